@@ -19,18 +19,84 @@
 // OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION
 // WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
 //
+use std::any::Any;
 use std::fmt::{Debug, Display};
 
 /// An error that can wrap an inner error that can be retrieved, if
 /// present, via downcasting
+///
+/// This trait is object-safe so that errors of different types can be
+/// stored as `Box<dyn HasOtherError>`: generic downcasting by reference
+/// is provided by `HasOtherErrorExt`
 pub trait HasOtherError {
     /// Returns `true` if error wraps an inner error; `false` otherwise
     fn is_other(&self) -> bool;
 
+    /// Returns the wrapped `anyhow::Error` as `Any` if error wraps an
+    /// inner error; returns `None` otherwise
+    fn downcast_other_any(&self) -> Option<&dyn Any>;
+
+    /// Attempt to take ownership of the inner error as type `E`;
+    /// returns the original error unchanged if there is no inner error
+    /// or if the inner error is not of type `E`
+    fn downcast_other<E>(self) -> Result<E, Self>
+    where
+        Self: Sized,
+        E: Debug + Display + Send + Sync + 'static;
+}
+
+/// Convenience methods available on every `HasOtherError`, including
+/// trait objects
+pub trait HasOtherErrorExt: HasOtherError {
     /// Attempt to downcast to type `E` if error wraps an inner error;
     /// returns `None` if there is no inner error or if the inner error
     /// is not of type `E`
     fn downcast_other_ref<E>(&self) -> Option<&E>
     where
-        E: Debug + Display + Send + Sync + 'static;
+        E: Debug + Display + Send + Sync + 'static,
+    {
+        let any = self.downcast_other_any()?;
+        any.downcast_ref::<anyhow::Error>().map_or_else(
+            || any.downcast_ref::<E>(),
+            |inner| inner.downcast_ref::<E>(),
+        )
+    }
+}
+
+impl<T> HasOtherErrorExt for T where T: HasOtherError + ?Sized {}
+
+#[cfg(test)]
+mod tests {
+    use super::{HasOtherError, HasOtherErrorExt};
+    use crate::{read_json_file, safe_write_file, FileReadError};
+    use anyhow::Result;
+    use serde_json::Value;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_trait_objects() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.json");
+        safe_write_file(&path, "{}", false)?;
+        let Err(json_error) = read_json_file::<Value>(&temp_dir.path().join("missing.json")) else {
+            panic!("read_json_file must fail")
+        };
+        let Err(write_error) = safe_write_file(&path, "{}", false) else {
+            panic!("safe_write_file must fail")
+        };
+
+        // Act
+        let errors: Vec<Box<dyn HasOtherError>> = vec![Box::new(json_error), Box::new(write_error)];
+
+        // Assert
+        assert!(errors[0].is_other());
+        assert!(errors[0]
+            .downcast_other_ref::<FileReadError>()
+            .expect("must be Some")
+            .is_not_found());
+        assert!(!errors[1].is_other());
+        assert!(errors[1].downcast_other_ref::<FileReadError>().is_none());
+        Ok(())
+    }
 }
@@ -0,0 +1,283 @@
+// Copyright (c) 2023 Richard Cook
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the
+// "Software"), to deal in the Software without restriction, including
+// without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to
+// permit persons to whom the Software is furnished to do so, subject to
+// the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE
+// LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION
+// WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+//
+use super::json::read_json_file;
+use super::toml::read_toml_file;
+use super::yaml::read_yaml_file;
+use crate::error::HasOtherError;
+use anyhow::Error as AnyhowError;
+use serde_json::{Map, Value};
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::error::Error as StdError;
+use std::fmt::{Debug, Display};
+use std::path::{Path, PathBuf};
+use std::result::Result as StdResult;
+use thiserror::Error;
+
+#[allow(unused)]
+#[derive(Debug, PartialEq)]
+#[non_exhaustive]
+pub enum ConfigErrorKind {
+    UnsupportedFormat,
+    Other,
+}
+
+#[derive(Debug, Error, PartialEq)]
+#[error(transparent)]
+pub struct ConfigError(#[from] ConfigErrorImpl);
+
+impl ConfigError {
+    #[allow(unused)]
+    #[must_use]
+    pub const fn kind(&self) -> ConfigErrorKind {
+        match self.0 {
+            ConfigErrorImpl::UnsupportedFormat(_) => ConfigErrorKind::UnsupportedFormat,
+            _ => ConfigErrorKind::Other,
+        }
+    }
+
+    /// Returns whether the error is of the given kind
+    #[allow(unused)]
+    #[allow(clippy::needless_pass_by_value)]
+    #[must_use]
+    pub fn is_kind(&self, kind: ConfigErrorKind) -> bool {
+        self.kind() == kind
+    }
+
+    #[allow(unused)]
+    #[must_use]
+    pub fn is_unsupported_format(&self) -> bool {
+        self.kind() == ConfigErrorKind::UnsupportedFormat
+    }
+
+    #[allow(unused)]
+    #[must_use]
+    pub fn is_other(&self) -> bool {
+        self.kind() == ConfigErrorKind::Other
+    }
+
+    fn other<E>(e: E) -> Self
+    where
+        E: StdError + Send + Sync + 'static,
+    {
+        Self(ConfigErrorImpl::Other(AnyhowError::new(e)))
+    }
+}
+
+impl HasOtherError for ConfigError {
+    fn is_other(&self) -> bool {
+        self.is_other()
+    }
+
+    fn downcast_other_any(&self) -> Option<&dyn Any> {
+        if let ConfigErrorImpl::Other(ref inner) = self.0 {
+            Some(inner)
+        } else {
+            None
+        }
+    }
+
+    fn downcast_other<E>(self) -> StdResult<E, Self>
+    where
+        E: Display + Debug + Send + Sync + 'static,
+    {
+        match self.0 {
+            ConfigErrorImpl::Other(inner) => inner
+                .downcast::<E>()
+                .map_err(|inner| Self(ConfigErrorImpl::Other(inner))),
+            inner => Err(Self(inner)),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+enum ConfigErrorImpl {
+    #[error("Configuration file {0} is not in a supported format")]
+    UnsupportedFormat(PathBuf),
+    #[error(transparent)]
+    Other(AnyhowError),
+}
+
+impl PartialEq for ConfigErrorImpl {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::UnsupportedFormat(a), Self::UnsupportedFormat(b)) => a == b,
+            (Self::Other(_), Self::Other(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Merges every file named `file_name` in `start_dir` and its ancestors
+///
+/// Files nearer to `start_dir` take precedence. Also returns, for each
+/// dotted key, the path of the file that supplied its value
+#[allow(unused)]
+pub fn read_layered_with_provenance(
+    file_name: &str,
+    start_dir: impl AsRef<Path>,
+) -> StdResult<(Value, BTreeMap<String, PathBuf>), ConfigError> {
+    let start_dir = start_dir.as_ref();
+    let mut paths = Vec::new();
+    let mut dir = Some(start_dir);
+    while let Some(d) = dir {
+        let path = d.join(file_name);
+        if path.is_file() {
+            paths.push(path);
+        }
+        dir = d.parent();
+    }
+
+    let mut value = Value::Object(Map::new());
+    let mut provenance = BTreeMap::new();
+    for path in paths.iter().rev() {
+        let layer = read_config_value(path)?;
+        merge_with_provenance(&mut value, layer, "", path, &mut provenance);
+    }
+
+    Ok((value, provenance))
+}
+
+fn read_config_value(path: &Path) -> StdResult<Value, ConfigError> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_lowercase);
+    match extension.as_deref() {
+        Some("json") => read_json_file(path).map_err(ConfigError::other),
+        Some("toml") => read_toml_file(path).map_err(ConfigError::other),
+        Some("yaml" | "yml") => read_yaml_file(path).map_err(ConfigError::other),
+        _ => Err(ConfigError(ConfigErrorImpl::UnsupportedFormat(
+            path.to_path_buf(),
+        ))),
+    }
+}
+
+fn merge_with_provenance(
+    base: &mut Value,
+    overlay: Value,
+    key: &str,
+    path: &Path,
+    provenance: &mut BTreeMap<String, PathBuf>,
+) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (k, v) in overlay {
+                let child_key = join_key(key, &k);
+                if let Some(b) = base.get_mut(&k) {
+                    merge_with_provenance(b, v, &child_key, path, provenance);
+                } else {
+                    record_provenance(&v, &child_key, path, provenance);
+                    base.insert(k, v);
+                }
+            }
+        }
+        (base, overlay) => {
+            let prefix = format!("{key}.");
+            provenance.retain(|k, _| k != key && !k.starts_with(&prefix));
+            record_provenance(&overlay, key, path, provenance);
+            *base = overlay;
+        }
+    }
+}
+
+fn record_provenance(
+    value: &Value,
+    key: &str,
+    path: &Path,
+    provenance: &mut BTreeMap<String, PathBuf>,
+) {
+    match value {
+        Value::Object(m) if !m.is_empty() => {
+            for (k, v) in m {
+                record_provenance(v, &join_key(key, k), path, provenance);
+            }
+        }
+        _ => {
+            provenance.insert(key.to_string(), path.to_path_buf());
+        }
+    }
+}
+
+fn join_key(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() {
+        key.to_string()
+    } else {
+        format!("{prefix}.{key}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_layered_with_provenance, ConfigErrorKind};
+    use anyhow::Result;
+    use serde_json::json;
+    use std::fs::{create_dir_all, write};
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_read_layered_with_provenance_succeeds() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let start_dir = temp_dir.path().join("aaa").join("bbb");
+        create_dir_all(&start_dir)?;
+        let outer_path = temp_dir.path().join("config.toml");
+        let inner_path = start_dir.join("config.toml");
+        write(
+            &outer_path,
+            "name = \"outer\"\n[server]\nhost = \"localhost\"\nport = 8080\n",
+        )?;
+        write(&inner_path, "[server]\nport = 9090\n")?;
+
+        // Act
+        let (value, provenance) = read_layered_with_provenance("config.toml", &start_dir)?;
+
+        // Assert
+        assert_eq!(
+            json!({"name": "outer", "server": {"host": "localhost", "port": 9090}}),
+            value
+        );
+        assert_eq!(3, provenance.len());
+        assert_eq!(Some(&outer_path), provenance.get("name"));
+        assert_eq!(Some(&outer_path), provenance.get("server.host"));
+        assert_eq!(Some(&inner_path), provenance.get("server.port"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_layered_with_provenance_unsupported_format_fails() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        write(temp_dir.path().join("config.ini"), "name=value")?;
+
+        // Act
+        let Err(e) = read_layered_with_provenance("config.ini", temp_dir.path()) else {
+            panic!("read_layered_with_provenance must fail")
+        };
+
+        // Assert
+        assert_eq!(ConfigErrorKind::UnsupportedFormat, e.kind());
+        assert!(e.is_unsupported_format());
+        assert!(!e.is_other());
+        Ok(())
+    }
+}
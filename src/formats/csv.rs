@@ -0,0 +1,343 @@
+// Copyright (c) 2023 Richard Cook
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the
+// "Software"), to deal in the Software without restriction, including
+// without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to
+// permit persons to whom the Software is furnished to do so, subject to
+// the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE
+// LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION
+// WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+//
+use crate::error::HasOtherError;
+use crate::fs::read_text_file_no_bom;
+use anyhow::Error as AnyhowError;
+use csv::{Error as CsvCrateError, ErrorKind as CsvCrateErrorKind, Position, Reader};
+use serde::de::DeserializeOwned;
+use std::any::Any;
+use std::error::Error as StdError;
+use std::fmt::{Debug, Display};
+use std::path::{Path, PathBuf};
+use std::result::Result as StdResult;
+use thiserror::Error;
+
+#[allow(unused)]
+#[derive(Debug, PartialEq)]
+#[non_exhaustive]
+pub enum CsvErrorKind {
+    Data,
+    Syntax,
+    Other,
+}
+
+#[derive(Debug, Error, PartialEq)]
+#[error(transparent)]
+pub struct CsvError(#[from] CsvErrorImpl);
+
+impl CsvError {
+    #[allow(unused)]
+    #[must_use]
+    pub const fn kind(&self) -> CsvErrorKind {
+        match self.0 {
+            CsvErrorImpl::Data { .. } => CsvErrorKind::Data,
+            CsvErrorImpl::Syntax { .. } => CsvErrorKind::Syntax,
+            _ => CsvErrorKind::Other,
+        }
+    }
+
+    /// Returns whether the error is of the given kind
+    #[allow(unused)]
+    #[allow(clippy::needless_pass_by_value)]
+    #[must_use]
+    pub fn is_kind(&self, kind: CsvErrorKind) -> bool {
+        self.kind() == kind
+    }
+
+    #[allow(unused)]
+    #[must_use]
+    pub fn is_data(&self) -> bool {
+        self.kind() == CsvErrorKind::Data
+    }
+
+    #[allow(unused)]
+    #[must_use]
+    pub fn is_syntax(&self) -> bool {
+        self.kind() == CsvErrorKind::Syntax
+    }
+
+    #[allow(unused)]
+    #[must_use]
+    pub fn is_other(&self) -> bool {
+        self.kind() == CsvErrorKind::Other
+    }
+
+    /// Returns the number of the record that failed to parse, if known,
+    /// where the header row is record 0 and the first data row is
+    /// record 1
+    #[allow(unused)]
+    #[must_use]
+    pub const fn record(&self) -> Option<u64> {
+        match self.0 {
+            CsvErrorImpl::Data { record, .. } | CsvErrorImpl::Syntax { record, .. } => record,
+            CsvErrorImpl::Other(_) => None,
+        }
+    }
+
+    /// Returns the path of the file that failed to parse
+    #[allow(unused)]
+    #[must_use]
+    pub fn path(&self) -> Option<&Path> {
+        match self.0 {
+            CsvErrorImpl::Data { ref path, .. } | CsvErrorImpl::Syntax { ref path, .. } => {
+                Some(path)
+            }
+            CsvErrorImpl::Other(_) => None,
+        }
+    }
+
+    fn other<E>(e: E) -> Self
+    where
+        E: StdError + Send + Sync + 'static,
+    {
+        Self(CsvErrorImpl::Other(AnyhowError::new(e)))
+    }
+
+    fn convert(e: CsvCrateError, path: &Path) -> Self {
+        let record = e.position().map(Position::record);
+        let path = path.to_path_buf();
+        match e.kind() {
+            CsvCrateErrorKind::Deserialize { err, .. } => Self(CsvErrorImpl::Data {
+                message: err.to_string(),
+                path,
+                record,
+            }),
+            CsvCrateErrorKind::UnequalLengths {
+                expected_len, len, ..
+            } => Self(CsvErrorImpl::Syntax {
+                message: format!("Expected {expected_len} fields but found {len}"),
+                path,
+                record,
+            }),
+            CsvCrateErrorKind::Utf8 { err, .. } => Self(CsvErrorImpl::Syntax {
+                message: err.to_string(),
+                path,
+                record,
+            }),
+            _ => Self::other(e),
+        }
+    }
+}
+
+impl HasOtherError for CsvError {
+    fn is_other(&self) -> bool {
+        self.is_other()
+    }
+
+    fn downcast_other_any(&self) -> Option<&dyn Any> {
+        if let CsvErrorImpl::Other(ref inner) = self.0 {
+            Some(inner)
+        } else {
+            None
+        }
+    }
+
+    fn downcast_other<E>(self) -> StdResult<E, Self>
+    where
+        E: Display + Debug + Send + Sync + 'static,
+    {
+        match self.0 {
+            CsvErrorImpl::Other(inner) => inner
+                .downcast::<E>()
+                .map_err(|inner| Self(CsvErrorImpl::Other(inner))),
+            inner => Err(Self(inner)),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+enum CsvErrorImpl {
+    #[error("{message}{} in {path}", format_record(*.record))]
+    Data {
+        message: String,
+        path: PathBuf,
+        record: Option<u64>,
+    },
+    #[error("{message}{} in {path}", format_record(*.record))]
+    Syntax {
+        message: String,
+        path: PathBuf,
+        record: Option<u64>,
+    },
+    #[error(transparent)]
+    Other(AnyhowError),
+}
+
+impl PartialEq for CsvErrorImpl {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (
+                Self::Data {
+                    path: a,
+                    record: record_a,
+                    ..
+                },
+                Self::Data {
+                    path: b,
+                    record: record_b,
+                    ..
+                },
+            )
+            | (
+                Self::Syntax {
+                    path: a,
+                    record: record_a,
+                    ..
+                },
+                Self::Syntax {
+                    path: b,
+                    record: record_b,
+                    ..
+                },
+            ) => a == b && record_a == record_b,
+            (Self::Other(_), Self::Other(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+fn format_record(record: Option<u64>) -> String {
+    record
+        .map(|r| format!(" at record {r}"))
+        .unwrap_or_default()
+}
+
+/// Reads the CSV file at `path`, which must start with a header row, into
+/// a vector of records
+#[allow(unused)]
+pub fn read_csv_file<T>(path: impl AsRef<Path>) -> StdResult<Vec<T>, CsvError>
+where
+    T: DeserializeOwned,
+{
+    let path = path.as_ref();
+    let s = read_text_file_no_bom(path).map_err(CsvError::other)?;
+    Reader::from_reader(s.as_bytes())
+        .deserialize::<T>()
+        .collect::<StdResult<Vec<_>, _>>()
+        .map_err(|e| CsvError::convert(e, path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_csv_file, CsvErrorKind};
+    use crate::error::HasOtherErrorExt;
+    use crate::FileReadError;
+    use anyhow::Result;
+    use serde::Deserialize;
+    use std::fs::write;
+    use tempdir::TempDir;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Record {
+        name: String,
+        count: u32,
+    }
+
+    #[test]
+    fn test_read_csv_file_succeeds() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.csv");
+        write(&path, "name,count\naaa,1\nbbb,2\n")?;
+
+        // Act
+        let records = read_csv_file::<Record>(&path)?;
+
+        // Assert
+        assert_eq!(
+            vec![
+                Record {
+                    name: String::from("aaa"),
+                    count: 1
+                },
+                Record {
+                    name: String::from("bbb"),
+                    count: 2
+                }
+            ],
+            records
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_csv_file_type_mismatch_fails() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.csv");
+        write(&path, "name,count\naaa,1\nbbb,xyz\n")?;
+
+        // Act
+        let Err(e) = read_csv_file::<Record>(&path) else {
+            panic!("read_csv_file must fail")
+        };
+
+        // Assert
+        assert_eq!(CsvErrorKind::Data, e.kind());
+        assert!(e.is_data());
+        assert_eq!(Some(2), e.record());
+        assert_eq!(Some(path.as_path()), e.path());
+        let message = format!("{e}");
+        assert!(message.contains("at record 2"));
+        assert!(message.contains(path.to_str().expect("must be valid string")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_csv_file_unequal_lengths_fails() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.csv");
+        write(&path, "name,count\naaa,1,extra\n")?;
+
+        // Act
+        let Err(e) = read_csv_file::<Record>(&path) else {
+            panic!("read_csv_file must fail")
+        };
+
+        // Assert
+        assert_eq!(CsvErrorKind::Syntax, e.kind());
+        assert_eq!(Some(1), e.record());
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_csv_file_nonexistent_fails() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.csv");
+
+        // Act
+        let Err(e) = read_csv_file::<Record>(&path) else {
+            panic!("read_csv_file must fail")
+        };
+
+        // Assert
+        assert!(e.is_other());
+        assert_eq!(None, e.record());
+        assert!(e
+            .downcast_other_ref::<FileReadError>()
+            .expect("must be Some")
+            .is_not_found());
+        Ok(())
+    }
+}
@@ -0,0 +1,119 @@
+// Copyright (c) 2023 Richard Cook
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the
+// "Software"), to deal in the Software without restriction, including
+// without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to
+// permit persons to whom the Software is furnished to do so, subject to
+// the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE
+// LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION
+// WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+//
+use super::position::SourceExcerpt;
+use colored::Colorize;
+use std::fmt::Write;
+use std::path::Path;
+
+// Renders an error in the style of a compiler diagnostic, including a
+// snippet of the offending line with a caret under the error position
+// when both the source text and the position are known
+pub fn render_diagnostic(
+    message: &str,
+    path: Option<&Path>,
+    excerpt: Option<&SourceExcerpt>,
+    line_col: Option<(usize, usize)>,
+    use_color: bool,
+) -> String {
+    let paint = |s: &str, f: fn(&str) -> String| {
+        if use_color {
+            f(s)
+        } else {
+            s.to_string()
+        }
+    };
+    let error = paint("error", |s| s.red().bold().to_string());
+    let message = paint(message, |s| s.bold().to_string());
+
+    let mut output = format!("{error}: {message}");
+
+    let snippet = match (excerpt, line_col) {
+        (Some(excerpt), Some((line, column))) => {
+            excerpt.line(line).map(|text| (line, column, text))
+        }
+        _ => None,
+    };
+
+    let width = snippet.map_or(1, |(line, _, _)| line.to_string().len());
+    let pad = " ".repeat(width);
+    let arrow = paint("-->", |s| s.blue().bold().to_string());
+    let gutter = paint("|", |s| s.blue().bold().to_string());
+
+    if let Some(path) = path {
+        _ = write!(output, "\n{pad}{arrow} {}", path.display());
+        if let Some((line, column)) = line_col {
+            _ = write!(output, ":{line}:{column}");
+        }
+    }
+
+    if let Some((line, column, text)) = snippet {
+        let number = paint(&line.to_string(), |s| s.blue().bold().to_string());
+        let caret = paint("^", |s| s.red().bold().to_string());
+        let indent = " ".repeat(column.saturating_sub(1));
+        _ = write!(
+            output,
+            "\n{pad} {gutter}\n{number} {gutter} {text}\n{pad} {gutter} {indent}{caret}"
+        );
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render_diagnostic;
+    use crate::formats::position::SourceExcerpt;
+    use std::path::Path;
+
+    #[test]
+    fn test_render_diagnostic_plain() {
+        // Act
+        let output = render_diagnostic(
+            "expected value",
+            Some(Path::new("file.json")),
+            SourceExcerpt::new("{\n  \"aaa\": xxx\n}", 2).as_ref(),
+            Some((2, 10)),
+            false,
+        );
+
+        // Assert
+        assert_eq!(
+            "error: expected value\n --> file.json:2:10\n  |\n2 |   \"aaa\": xxx\n  |          ^",
+            output
+        );
+    }
+
+    #[test]
+    fn test_render_diagnostic_no_position() {
+        // Act
+        let output = render_diagnostic(
+            "file not found",
+            Some(Path::new("file.json")),
+            None,
+            None,
+            false,
+        );
+
+        // Assert
+        assert_eq!("error: file not found\n --> file.json", output);
+    }
+}
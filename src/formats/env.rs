@@ -0,0 +1,212 @@
+// Copyright (c) 2023 Richard Cook
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the
+// "Software"), to deal in the Software without restriction, including
+// without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to
+// permit persons to whom the Software is furnished to do so, subject to
+// the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE
+// LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION
+// WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+//
+use crate::fs::{read_text_file_no_bom, FileReadError};
+use std::path::Path;
+use std::result::Result as StdResult;
+
+/// Reads the `.env`-style file at `path` into a list of key-value pairs
+/// in file order
+///
+/// Each non-blank line that is not a `#` comment must have the form
+/// `KEY=VALUE`, optionally preceded by `export`. Double-quoted values
+/// support `\n`, `\r`, `\t`, `\\` and `\"` escapes, single-quoted values
+/// are taken literally and unquoted values end at a ` #` comment
+#[allow(unused)]
+pub fn read_env_file(path: impl AsRef<Path>) -> StdResult<Vec<(String, String)>, FileReadError> {
+    let path = path.as_ref();
+    let s = read_text_file_no_bom(path)?;
+    let mut pairs = Vec::new();
+    for (i, line) in s.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let pair =
+            parse_line(line).map_err(|message| FileReadError::malformed(path, i + 1, message))?;
+        pairs.push(pair);
+    }
+    Ok(pairs)
+}
+
+fn parse_line(line: &str) -> StdResult<(String, String), String> {
+    let line = line.strip_prefix("export ").map_or(line, str::trim_start);
+    let Some((key, value)) = line.split_once('=') else {
+        return Err(String::from("expected KEY=VALUE"));
+    };
+
+    let key = key.trim();
+    if key.is_empty()
+        || !key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+    {
+        return Err(format!("invalid key \"{key}\""));
+    }
+
+    let value = value.trim_start();
+    let (value, rest) = match value.chars().next() {
+        Some('"') => parse_double_quoted(&value[1..])?,
+        Some('\'') => {
+            let Some((value, rest)) = value[1..].split_once('\'') else {
+                return Err(String::from("unterminated single-quoted value"));
+            };
+            (String::from(value), rest)
+        }
+        _ => {
+            let end = value
+                .find(" #")
+                .or_else(|| value.find("\t#"))
+                .unwrap_or(value.len());
+            (String::from(value[..end].trim_end()), "")
+        }
+    };
+
+    let rest = rest.trim_start();
+    if !rest.is_empty() && !rest.starts_with('#') {
+        return Err(format!("unexpected characters \"{rest}\" after value"));
+    }
+
+    Ok((String::from(key), value))
+}
+
+fn parse_double_quoted(s: &str) -> StdResult<(String, &str), String> {
+    let mut value = String::new();
+    let mut chars = s.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Ok((value, &s[i + 1..])),
+            '\\' => match chars.next() {
+                Some((_, 'n')) => value.push('\n'),
+                Some((_, 'r')) => value.push('\r'),
+                Some((_, 't')) => value.push('\t'),
+                Some((_, c @ ('\\' | '"'))) => value.push(c),
+                Some((_, c)) => {
+                    value.push('\\');
+                    value.push(c);
+                }
+                None => break,
+            },
+            _ => value.push(c),
+        }
+    }
+    Err(String::from("unterminated double-quoted value"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::read_env_file;
+    use crate::fs::FileReadErrorKind;
+    use anyhow::Result;
+    use rstest::rstest;
+    use std::fs::write;
+    use tempdir::TempDir;
+
+    fn pairs(items: &[(&str, &str)]) -> Vec<(String, String)> {
+        items
+            .iter()
+            .map(|(k, v)| (String::from(*k), String::from(*v)))
+            .collect()
+    }
+
+    #[test]
+    fn test_read_env_file_succeeds() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join(".env");
+        write(
+            &path,
+            "# Comment\n\nAAA=aaa\n  BBB = bbb  # trailing comment\nexport CCC=ccc\nDDD=\n",
+        )?;
+
+        // Act
+        let values = read_env_file(&path)?;
+
+        // Assert
+        assert_eq!(
+            pairs(&[("AAA", "aaa"), ("BBB", "bbb"), ("CCC", "ccc"), ("DDD", "")]),
+            values
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_env_file_quoted() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join(".env");
+        write(
+            &path,
+            "AAA=\"hello # world\"\nBBB=\"line0\\nline1 \\\"quoted\\\"\" # comment\nCCC='single \\n # literal'\nDDD=url#fragment\n",
+        )?;
+
+        // Act
+        let values = read_env_file(&path)?;
+
+        // Assert
+        assert_eq!(
+            pairs(&[
+                ("AAA", "hello # world"),
+                ("BBB", "line0\nline1 \"quoted\""),
+                ("CCC", "single \\n # literal"),
+                ("DDD", "url#fragment"),
+            ]),
+            values
+        );
+        Ok(())
+    }
+
+    #[rstest]
+    #[case("AAA=aaa\nnot a pair\n", 2)]
+    #[case("AAA=aaa\n\n# comment\nBBB=\"unterminated\n", 4)]
+    #[case("AAA='unterminated\n", 1)]
+    #[case("AAA=\"value\" extra\n", 1)]
+    #[case("AAA=aaa\nBAD KEY=value\n", 2)]
+    #[case("=value\n", 1)]
+    fn test_read_env_file_malformed_fails(
+        #[case] content: &str,
+        #[case] expected_line: usize,
+    ) -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join(".env");
+        write(&path, content)?;
+
+        // Act
+        let Err(e) = read_env_file(&path) else {
+            panic!("read_env_file must fail")
+        };
+
+        // Assert
+        assert_eq!(
+            FileReadErrorKind::Malformed {
+                line: expected_line
+            },
+            e.kind()
+        );
+        assert!(e.is_malformed());
+        assert_eq!(Some(path.as_path()), e.path());
+        let message = format!("{e}");
+        assert!(message.contains(&format!("line {expected_line}")));
+        Ok(())
+    }
+}
@@ -0,0 +1,402 @@
+// Copyright (c) 2023 Richard Cook
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the
+// "Software"), to deal in the Software without restriction, including
+// without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to
+// permit persons to whom the Software is furnished to do so, subject to
+// the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE
+// LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION
+// WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+//
+use super::json::{read_json_file, JsonError};
+use super::toml::{read_toml_file, TomlError};
+use super::yaml::{read_yaml_file, YamlError};
+use crate::error::HasOtherError;
+use crate::fs::{read_text_file_no_bom, safe_write_file};
+use anyhow::Error as AnyhowError;
+use serde::de::{DeserializeOwned, IgnoredAny};
+use serde::Serialize;
+use std::any::Any;
+use std::error::Error as StdError;
+use std::fmt::{Debug, Display};
+use std::path::Path;
+use std::result::Result as StdResult;
+use thiserror::Error;
+
+#[allow(unused)]
+#[derive(Debug, PartialEq)]
+#[non_exhaustive]
+pub enum FormatErrorKind {
+    Json,
+    Toml,
+    Yaml,
+    Other,
+}
+
+#[derive(Debug, Error, PartialEq)]
+#[error(transparent)]
+pub struct FormatError(#[from] FormatErrorImpl);
+
+impl FormatError {
+    #[allow(unused)]
+    #[must_use]
+    pub const fn kind(&self) -> FormatErrorKind {
+        match self.0 {
+            FormatErrorImpl::Json(_) => FormatErrorKind::Json,
+            FormatErrorImpl::Toml(_) => FormatErrorKind::Toml,
+            FormatErrorImpl::Yaml(_) => FormatErrorKind::Yaml,
+            FormatErrorImpl::Other(_) => FormatErrorKind::Other,
+        }
+    }
+
+    /// Returns whether the error is of the given kind
+    #[allow(unused)]
+    #[allow(clippy::needless_pass_by_value)]
+    #[must_use]
+    pub fn is_kind(&self, kind: FormatErrorKind) -> bool {
+        self.kind() == kind
+    }
+
+    #[allow(unused)]
+    #[must_use]
+    pub fn is_other(&self) -> bool {
+        self.kind() == FormatErrorKind::Other
+    }
+
+    fn other<E>(e: E) -> Self
+    where
+        E: StdError + Send + Sync + 'static,
+    {
+        Self(FormatErrorImpl::Other(AnyhowError::new(e)))
+    }
+}
+
+impl HasOtherError for FormatError {
+    fn is_other(&self) -> bool {
+        self.is_other()
+    }
+
+    fn downcast_other_any(&self) -> Option<&dyn Any> {
+        if let FormatErrorImpl::Other(ref inner) = self.0 {
+            Some(inner)
+        } else {
+            None
+        }
+    }
+
+    fn downcast_other<E>(self) -> StdResult<E, Self>
+    where
+        E: Display + Debug + Send + Sync + 'static,
+    {
+        match self.0 {
+            FormatErrorImpl::Other(inner) => inner
+                .downcast::<E>()
+                .map_err(|inner| Self(FormatErrorImpl::Other(inner))),
+            inner => Err(Self(inner)),
+        }
+    }
+}
+
+impl From<JsonError> for FormatError {
+    fn from(e: JsonError) -> Self {
+        Self(FormatErrorImpl::Json(e))
+    }
+}
+
+impl From<TomlError> for FormatError {
+    fn from(e: TomlError) -> Self {
+        Self(FormatErrorImpl::Toml(e))
+    }
+}
+
+impl From<YamlError> for FormatError {
+    fn from(e: YamlError) -> Self {
+        Self(FormatErrorImpl::Yaml(e))
+    }
+}
+
+#[derive(Debug, Error)]
+enum FormatErrorImpl {
+    #[error(transparent)]
+    Json(JsonError),
+    #[error(transparent)]
+    Toml(TomlError),
+    #[error(transparent)]
+    Yaml(YamlError),
+    #[error(transparent)]
+    Other(AnyhowError),
+}
+
+impl PartialEq for FormatErrorImpl {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Json(a), Self::Json(b)) => a == b,
+            (Self::Toml(a), Self::Toml(b)) => a == b,
+            (Self::Yaml(a), Self::Yaml(b)) => a == b,
+            (Self::Other(_), Self::Other(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+/// A serialization format that values can be read from and written to
+/// files in, allowing code to be generic over the format
+pub trait Format {
+    fn read<T>(path: impl AsRef<Path>) -> StdResult<T, FormatError>
+    where
+        T: DeserializeOwned;
+
+    fn write<T>(path: impl AsRef<Path>, value: &T, overwrite: bool) -> StdResult<(), FormatError>
+    where
+        T: Serialize;
+}
+
+/// JSON, written pretty-printed
+pub struct Json;
+
+impl Format for Json {
+    fn read<T>(path: impl AsRef<Path>) -> StdResult<T, FormatError>
+    where
+        T: DeserializeOwned,
+    {
+        Ok(read_json_file(path)?)
+    }
+
+    fn write<T>(path: impl AsRef<Path>, value: &T, overwrite: bool) -> StdResult<(), FormatError>
+    where
+        T: Serialize,
+    {
+        let s = serde_json::to_string_pretty(value).map_err(JsonError::other)?;
+        safe_write_file(path, s, overwrite).map_err(JsonError::other)?;
+        Ok(())
+    }
+}
+
+/// TOML
+pub struct Toml;
+
+impl Format for Toml {
+    fn read<T>(path: impl AsRef<Path>) -> StdResult<T, FormatError>
+    where
+        T: DeserializeOwned,
+    {
+        Ok(read_toml_file(path)?)
+    }
+
+    fn write<T>(path: impl AsRef<Path>, value: &T, overwrite: bool) -> StdResult<(), FormatError>
+    where
+        T: Serialize,
+    {
+        let s = toml::to_string(value).map_err(TomlError::other)?;
+        safe_write_file(path, s, overwrite).map_err(TomlError::other)?;
+        Ok(())
+    }
+}
+
+/// YAML
+pub struct Yaml;
+
+impl Format for Yaml {
+    fn read<T>(path: impl AsRef<Path>) -> StdResult<T, FormatError>
+    where
+        T: DeserializeOwned,
+    {
+        Ok(read_yaml_file(path)?)
+    }
+
+    fn write<T>(path: impl AsRef<Path>, value: &T, overwrite: bool) -> StdResult<(), FormatError>
+    where
+        T: Serialize,
+    {
+        let s = serde_yaml::to_string(value).map_err(YamlError::other)?;
+        safe_write_file(path, s, overwrite).map_err(YamlError::other)?;
+        Ok(())
+    }
+}
+
+/// Reads the file at `path` in the format indicated by its extension,
+/// one of `json`, `toml`, `yaml` or `yml` ignoring case
+///
+/// If the extension is missing or not recognized the format is detected
+/// from the contents of the file by trying JSON, then TOML and finally
+/// YAML
+#[allow(unused)]
+pub fn load<T>(path: impl AsRef<Path>) -> StdResult<T, FormatError>
+where
+    T: DeserializeOwned,
+{
+    let path = path.as_ref();
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("json") => Json::read(path),
+        Some("toml") => Toml::read(path),
+        Some("yaml" | "yml") => Yaml::read(path),
+        _ => {
+            let s = read_text_file_no_bom(path).map_err(FormatError::other)?;
+            if serde_json::from_str::<IgnoredAny>(&s).is_ok() {
+                Json::read(path)
+            } else if toml::from_str::<IgnoredAny>(&s).is_ok() {
+                Toml::read(path)
+            } else {
+                Yaml::read(path)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{load, Format, FormatErrorKind, Json, Toml, Yaml};
+    use crate::error::HasOtherErrorExt;
+    use crate::fs::FileReadError;
+    use anyhow::Result;
+    use rstest::rstest;
+    use serde::{Deserialize, Serialize};
+    use std::fs::write;
+    use std::path::PathBuf;
+    use tempdir::TempDir;
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Record {
+        message: String,
+        values: Vec<i32>,
+    }
+
+    fn round_trip<F>(path: PathBuf) -> Result<Record>
+    where
+        F: Format,
+    {
+        let value = Record {
+            message: String::from("hello-world"),
+            values: vec![1, 2, 3],
+        };
+        F::write(&path, &value, false)?;
+        Ok(F::read(path)?)
+    }
+
+    #[test]
+    fn test_round_trip() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let expected = Record {
+            message: String::from("hello-world"),
+            values: vec![1, 2, 3],
+        };
+
+        // Act
+        let json = round_trip::<Json>(temp_dir.path().join("file.json"))?;
+        let toml = round_trip::<Toml>(temp_dir.path().join("file.toml"))?;
+        let yaml = round_trip::<Yaml>(temp_dir.path().join("file.yaml"))?;
+
+        // Assert
+        assert_eq!(expected, json);
+        assert_eq!(expected, toml);
+        assert_eq!(expected, yaml);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_invalid_fails() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file");
+        write(&path, "xxx{")?;
+
+        // Act
+        let Err(e) = Toml::read::<Record>(&path) else {
+            panic!("read must fail")
+        };
+
+        // Assert
+        assert_eq!(FormatErrorKind::Toml, e.kind());
+        Ok(())
+    }
+
+    #[rstest]
+    #[case("file.json", "{\"message\": \"hello-world\", \"values\": [1, 2, 3]}")]
+    #[case("file.JSON", "{\"message\": \"hello-world\", \"values\": [1, 2, 3]}")]
+    #[case("file.yaml", "message: hello-world\nvalues: [1, 2, 3]\n")]
+    #[case("file.yml", "message: hello-world\nvalues: [1, 2, 3]\n")]
+    #[case("file.toml", "message = \"hello-world\"\nvalues = [1, 2, 3]\n")]
+    #[case("file", "{\"message\": \"hello-world\", \"values\": [1, 2, 3]}")]
+    #[case("file", "message = \"hello-world\"\nvalues = [1, 2, 3]\n")]
+    #[case("file", "message: hello-world\nvalues: [1, 2, 3]\n")]
+    #[case("file.conf", "message: hello-world\nvalues: [1, 2, 3]\n")]
+    fn test_load(#[case] file_name: &str, #[case] content: &str) -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join(file_name);
+        write(&path, content)?;
+
+        // Act
+        let value = load::<Record>(&path)?;
+
+        // Assert
+        assert_eq!(
+            Record {
+                message: String::from("hello-world"),
+                values: vec![1, 2, 3],
+            },
+            value
+        );
+        Ok(())
+    }
+
+    #[rstest]
+    #[case(FormatErrorKind::Json, "file.json")]
+    #[case(FormatErrorKind::Toml, "file.toml")]
+    #[case(FormatErrorKind::Yaml, "file.yaml")]
+    #[case(FormatErrorKind::Yaml, "file")]
+    fn test_load_invalid_fails(
+        #[case] expected_kind: FormatErrorKind,
+        #[case] file_name: &str,
+    ) -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join(file_name);
+        write(&path, "message: [unclosed\n")?;
+
+        // Act
+        let Err(e) = load::<Record>(&path) else {
+            panic!("load must fail")
+        };
+
+        // Assert
+        assert_eq!(expected_kind, e.kind());
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_extensionless_missing_fails() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file");
+
+        // Act
+        let Err(e) = load::<Record>(&path) else {
+            panic!("load must fail")
+        };
+
+        // Assert
+        assert!(e.is_other());
+        assert!(e
+            .downcast_other_ref::<FileReadError>()
+            .expect("must be Some")
+            .is_not_found());
+        Ok(())
+    }
+}
@@ -0,0 +1,206 @@
+// Copyright (c) 2023 Richard Cook
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the
+// "Software"), to deal in the Software without restriction, including
+// without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to
+// permit persons to whom the Software is furnished to do so, subject to
+// the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE
+// LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION
+// WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+//
+use crate::error::HasOtherError;
+use crate::fs::read_text_file_no_bom;
+use anyhow::Error as AnyhowError;
+use serde::de::DeserializeOwned;
+use serde_ini::de::Error as SerdeIniError;
+use std::any::Any;
+use std::error::Error as StdError;
+use std::fmt::{Debug, Display};
+use std::path::{Path, PathBuf};
+use std::result::Result as StdResult;
+use thiserror::Error;
+
+#[allow(unused)]
+#[derive(Debug, PartialEq)]
+#[non_exhaustive]
+pub enum IniErrorKind {
+    Syntax,
+    Other,
+}
+
+#[derive(Debug, Error, PartialEq)]
+#[error(transparent)]
+pub struct IniError(#[from] IniErrorImpl);
+
+impl IniError {
+    #[allow(unused)]
+    #[must_use]
+    pub const fn kind(&self) -> IniErrorKind {
+        match self.0 {
+            IniErrorImpl::Syntax { .. } => IniErrorKind::Syntax,
+            _ => IniErrorKind::Other,
+        }
+    }
+
+    /// Returns whether the error is of the given kind
+    #[allow(unused)]
+    #[allow(clippy::needless_pass_by_value)]
+    #[must_use]
+    pub fn is_kind(&self, kind: IniErrorKind) -> bool {
+        self.kind() == kind
+    }
+
+    #[allow(unused)]
+    #[must_use]
+    pub fn is_syntax(&self) -> bool {
+        self.kind() == IniErrorKind::Syntax
+    }
+
+    #[allow(unused)]
+    #[must_use]
+    pub fn is_other(&self) -> bool {
+        self.kind() == IniErrorKind::Other
+    }
+
+    fn other<E>(e: E) -> Self
+    where
+        E: StdError + Send + Sync + 'static,
+    {
+        Self(IniErrorImpl::Other(AnyhowError::new(e)))
+    }
+
+    fn convert(e: &SerdeIniError, path: &Path) -> Self {
+        Self(IniErrorImpl::Syntax {
+            message: e.to_string(),
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+impl HasOtherError for IniError {
+    fn is_other(&self) -> bool {
+        self.is_other()
+    }
+
+    fn downcast_other_any(&self) -> Option<&dyn Any> {
+        if let IniErrorImpl::Other(ref inner) = self.0 {
+            Some(inner)
+        } else {
+            None
+        }
+    }
+
+    fn downcast_other<E>(self) -> StdResult<E, Self>
+    where
+        E: Display + Debug + Send + Sync + 'static,
+    {
+        match self.0 {
+            IniErrorImpl::Other(inner) => inner
+                .downcast::<E>()
+                .map_err(|inner| Self(IniErrorImpl::Other(inner))),
+            inner => Err(Self(inner)),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+enum IniErrorImpl {
+    #[error("{message} in {path}")]
+    Syntax { message: String, path: PathBuf },
+    #[error(transparent)]
+    Other(AnyhowError),
+}
+
+impl PartialEq for IniErrorImpl {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Syntax { path: a, .. }, Self::Syntax { path: b, .. }) => a == b,
+            (Self::Other(_), Self::Other(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+#[allow(unused)]
+pub fn read_ini_file<T>(path: impl AsRef<Path>) -> StdResult<T, IniError>
+where
+    T: DeserializeOwned,
+{
+    let path = path.as_ref();
+    let s = read_text_file_no_bom(path).map_err(IniError::other)?;
+    let value = serde_ini::from_str::<T>(&s).map_err(|e| IniError::convert(&e, path))?;
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_ini_file, IniErrorKind};
+    use anyhow::Result;
+    use serde::Deserialize;
+    use std::fs::write;
+    use tempdir::TempDir;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Config {
+        section: Section,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Section {
+        message: String,
+    }
+
+    #[test]
+    fn test_read_ini_file_succeeds() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.ini");
+        write(&path, "[section]\nmessage=hello-world\n")?;
+
+        // Act
+        let value = read_ini_file::<Config>(&path)?;
+
+        // Assert
+        assert_eq!(
+            Config {
+                section: Section {
+                    message: String::from("hello-world")
+                }
+            },
+            value
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_ini_file_invalid_fails() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.ini");
+        write(&path, "[section\nmessage=hello-world\n")?;
+
+        // Act
+        let Err(e) = read_ini_file::<Config>(&path) else {
+            panic!("read_ini_file must fail")
+        };
+
+        // Assert
+        assert_eq!(IniErrorKind::Syntax, e.kind());
+        assert!(e.is_syntax());
+        assert!(!e.is_other());
+        let message = format!("{e}");
+        assert!(message.contains(path.to_str().expect("must be valid string")));
+        Ok(())
+    }
+}
@@ -19,13 +19,20 @@
 // OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION
 // WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
 //
+use super::diagnostic::render_diagnostic;
+use super::position::{byte_column_to_char_column, offset_to_line_col, HasPosition, SourceExcerpt};
 use crate::error::HasOtherError;
-use crate::fs::read_text_file;
+#[cfg(feature = "flate2")]
+use crate::fs::open_file;
+use crate::fs::{append_file, read_text_file_no_bom, safe_write_file, SafeWriter, WriteOptions};
 use anyhow::Error as AnyhowError;
 use serde::de::DeserializeOwned;
-use serde_json::Error as SerdeJsonError;
+use serde::Serialize;
+use serde_json::{Error as SerdeJsonError, Value};
+use std::any::Any;
 use std::error::Error as StdError;
 use std::fmt::{Debug, Display};
+use std::ops::Range;
 use std::path::{Path, PathBuf};
 use std::result::Result as StdResult;
 use thiserror::Error;
@@ -41,7 +48,7 @@ pub enum JsonErrorKind {
     Other,
 }
 
-#[derive(Debug, Error)]
+#[derive(Debug, Error, PartialEq)]
 #[error(transparent)]
 pub struct JsonError(#[from] JsonErrorImpl);
 
@@ -50,7 +57,7 @@ impl JsonError {
     #[must_use]
     pub const fn kind(&self) -> JsonErrorKind {
         match self.0 {
-            JsonErrorImpl::Data { .. } => JsonErrorKind::Data,
+            JsonErrorImpl::Data { .. } | JsonErrorImpl::InvalidData { .. } => JsonErrorKind::Data,
             JsonErrorImpl::Eof { .. } => JsonErrorKind::Eof,
             JsonErrorImpl::Io { .. } => JsonErrorKind::Io,
             JsonErrorImpl::Syntax { .. } => JsonErrorKind::Syntax,
@@ -58,6 +65,14 @@ impl JsonError {
         }
     }
 
+    /// Returns whether the error is of the given kind
+    #[allow(unused)]
+    #[allow(clippy::needless_pass_by_value)]
+    #[must_use]
+    pub fn is_kind(&self, kind: JsonErrorKind) -> bool {
+        self.kind() == kind
+    }
+
     #[allow(unused)]
     #[must_use]
     pub fn is_data(&self) -> bool {
@@ -88,25 +103,137 @@ impl JsonError {
         self.kind() == JsonErrorKind::Other
     }
 
-    fn other<E>(e: E) -> Self
+    pub(super) fn other<E>(e: E) -> Self
     where
         E: StdError + Send + Sync + 'static,
     {
         Self(JsonErrorImpl::Other(AnyhowError::new(e)))
     }
 
-    fn convert(e: &SerdeJsonError, path: &Path) -> Self {
+    #[cfg(feature = "jsonc")]
+    pub(super) fn syntax(message: String, path: &Path) -> Self {
+        Self(JsonErrorImpl::Syntax {
+            message,
+            path: path.to_path_buf(),
+            line_col: None,
+            excerpt: None,
+        })
+    }
+
+    fn convert(e: &SerdeJsonError, path: &Path, contents: Option<&str>) -> Self {
         use serde_json::error::Category::*;
 
         let message = e.to_string();
         let path = path.to_path_buf();
+        let line_col = (e.line() > 0).then(|| {
+            let column = contents.map_or_else(
+                || e.column(),
+                |s| byte_column_to_char_column(s, e.line(), e.column()),
+            );
+            (e.line(), column)
+        });
+        let excerpt = contents
+            .zip(line_col)
+            .and_then(|(s, (line, _))| SourceExcerpt::new(s, line));
         Self(match e.classify() {
-            Data => JsonErrorImpl::Data { message, path },
-            Eof => JsonErrorImpl::Eof { message, path },
-            Io => JsonErrorImpl::Io { message, path },
-            Syntax => JsonErrorImpl::Syntax { message, path },
+            Data => JsonErrorImpl::Data {
+                message,
+                path,
+                line_col,
+                excerpt,
+            },
+            Eof => JsonErrorImpl::Eof {
+                message,
+                path,
+                line_col,
+                excerpt,
+            },
+            Io => JsonErrorImpl::Io {
+                message,
+                path,
+                line_col,
+                excerpt,
+            },
+            Syntax => JsonErrorImpl::Syntax {
+                message,
+                path,
+                line_col,
+                excerpt,
+            },
         })
     }
+
+    /// Formats the error as a multi-line diagnostic showing the
+    /// offending line of the source file where available
+    #[allow(unused)]
+    #[must_use]
+    pub fn display_pretty(&self, use_color: bool) -> String {
+        match self.0 {
+            JsonErrorImpl::Data {
+                ref message,
+                ref path,
+                line_col,
+                ref excerpt,
+            }
+            | JsonErrorImpl::Eof {
+                ref message,
+                ref path,
+                line_col,
+                ref excerpt,
+            }
+            | JsonErrorImpl::Io {
+                ref message,
+                ref path,
+                line_col,
+                ref excerpt,
+            }
+            | JsonErrorImpl::Syntax {
+                ref message,
+                ref path,
+                line_col,
+                ref excerpt,
+            } => render_diagnostic(message, Some(path), excerpt.as_ref(), line_col, use_color),
+            _ => render_diagnostic(&self.0.to_string(), None, None, None, use_color),
+        }
+    }
+}
+
+impl HasPosition for JsonError {
+    fn line_col(&self) -> Option<(usize, usize)> {
+        match self.0 {
+            JsonErrorImpl::Data { line_col, .. }
+            | JsonErrorImpl::Eof { line_col, .. }
+            | JsonErrorImpl::Io { line_col, .. }
+            | JsonErrorImpl::Syntax { line_col, .. } => line_col,
+            _ => None,
+        }
+    }
+
+    fn byte_span(&self) -> Option<Range<usize>> {
+        match self.0 {
+            JsonErrorImpl::Data {
+                line_col: Some((line, column)),
+                excerpt: Some(ref excerpt),
+                ..
+            }
+            | JsonErrorImpl::Eof {
+                line_col: Some((line, column)),
+                excerpt: Some(ref excerpt),
+                ..
+            }
+            | JsonErrorImpl::Io {
+                line_col: Some((line, column)),
+                excerpt: Some(ref excerpt),
+                ..
+            }
+            | JsonErrorImpl::Syntax {
+                line_col: Some((line, column)),
+                excerpt: Some(ref excerpt),
+                ..
+            } => excerpt.offset(line, column).map(|o| excerpt.char_span(o)),
+            _ => None,
+        }
+    }
 }
 
 impl HasOtherError for JsonError {
@@ -114,48 +241,568 @@ impl HasOtherError for JsonError {
         self.is_other()
     }
 
-    fn downcast_other_ref<E>(&self) -> Option<&E>
-    where
-        E: Display + Debug + Send + Sync + 'static,
-    {
+    fn downcast_other_any(&self) -> Option<&dyn Any> {
         if let JsonErrorImpl::Other(ref inner) = self.0 {
-            inner.downcast_ref::<E>()
+            Some(inner)
         } else {
             None
         }
     }
+
+    fn downcast_other<E>(self) -> StdResult<E, Self>
+    where
+        E: Display + Debug + Send + Sync + 'static,
+    {
+        match self.0 {
+            JsonErrorImpl::Other(inner) => inner
+                .downcast::<E>()
+                .map_err(|inner| Self(JsonErrorImpl::Other(inner))),
+            inner => Err(Self(inner)),
+        }
+    }
 }
 
 #[derive(Debug, Error)]
 enum JsonErrorImpl {
     #[error("{message} in {path}")]
-    Data { message: String, path: PathBuf },
+    Data {
+        message: String,
+        path: PathBuf,
+        line_col: Option<(usize, usize)>,
+        excerpt: Option<SourceExcerpt>,
+    },
     #[error("{message} in {path}")]
-    Eof { message: String, path: PathBuf },
+    Eof {
+        message: String,
+        path: PathBuf,
+        line_col: Option<(usize, usize)>,
+        excerpt: Option<SourceExcerpt>,
+    },
     #[error("{message} in {path}")]
-    Io { message: String, path: PathBuf },
+    Io {
+        message: String,
+        path: PathBuf,
+        line_col: Option<(usize, usize)>,
+        excerpt: Option<SourceExcerpt>,
+    },
     #[error("{message} in {path}")]
-    Syntax { message: String, path: PathBuf },
+    Syntax {
+        message: String,
+        path: PathBuf,
+        line_col: Option<(usize, usize)>,
+        excerpt: Option<SourceExcerpt>,
+    },
+    #[error("{message}")]
+    InvalidData { message: String },
     #[error(transparent)]
     Other(AnyhowError),
 }
 
+impl PartialEq for JsonErrorImpl {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Data { path: a, .. }, Self::Data { path: b, .. })
+            | (Self::Eof { path: a, .. }, Self::Eof { path: b, .. })
+            | (Self::Io { path: a, .. }, Self::Io { path: b, .. })
+            | (Self::Syntax { path: a, .. }, Self::Syntax { path: b, .. }) => a == b,
+            (Self::InvalidData { message: a }, Self::InvalidData { message: b }) => a == b,
+            (Self::Other(_), Self::Other(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+#[allow(unused)]
+pub fn read_json_file<T>(path: impl AsRef<Path>) -> StdResult<T, JsonError>
+where
+    T: DeserializeOwned,
+{
+    let path = path.as_ref();
+    let s = read_text_file_no_bom(path).map_err(JsonError::other)?;
+    let value =
+        serde_json::from_str::<T>(&s).map_err(|e| JsonError::convert(&e, path, Some(&s)))?;
+    Ok(value)
+}
+
+/// Reads the gzip-compressed JSON file at `path`
+#[cfg(feature = "flate2")]
+#[allow(unused)]
+pub fn read_json_gz_file<T>(path: impl AsRef<Path>) -> StdResult<T, JsonError>
+where
+    T: DeserializeOwned,
+{
+    use flate2::read::GzDecoder;
+    use std::io::{BufReader, Read};
+
+    let path = path.as_ref();
+    let file = open_file(path).map_err(JsonError::other)?;
+    let mut s = String::new();
+    GzDecoder::new(BufReader::new(file))
+        .read_to_string(&mut s)
+        .map_err(|e| JsonError::convert(&SerdeJsonError::io(e), path, None))?;
+    let value =
+        serde_json::from_str::<T>(&s).map_err(|e| JsonError::convert(&e, path, Some(&s)))?;
+    Ok(value)
+}
+
+/// Reads the JSON file at `path` and fails with a syntax error if arrays
+/// and objects are nested more than `max_depth` deep
+///
+/// The nesting is checked without recursion before deserializing so that
+/// adversarial input cannot exhaust the stack. `max_depth` is clamped to
+/// 127, the deepest nesting that `serde_json` ever accepts
+#[allow(unused)]
+pub fn read_json_file_bounded<T>(
+    path: impl AsRef<Path>,
+    max_depth: usize,
+) -> StdResult<T, JsonError>
+where
+    T: DeserializeOwned,
+{
+    const SERDE_JSON_MAX_DEPTH: usize = 127;
+
+    let path = path.as_ref();
+    let max_depth = max_depth.min(SERDE_JSON_MAX_DEPTH);
+    let s = read_text_file_no_bom(path).map_err(JsonError::other)?;
+    if let Some(offset) = find_depth_exceeded(&s, max_depth) {
+        let (line, column) = offset_to_line_col(&s, offset);
+        return Err(JsonError(JsonErrorImpl::Syntax {
+            message: format!("recursion limit of {max_depth} exceeded"),
+            path: path.to_path_buf(),
+            line_col: Some((line, column)),
+            excerpt: SourceExcerpt::new(&s, line),
+        }));
+    }
+    let value =
+        serde_json::from_str::<T>(&s).map_err(|e| JsonError::convert(&e, path, Some(&s)))?;
+    Ok(value)
+}
+
+// Returns the byte offset of the first opening bracket or brace nested
+// deeper than max_depth, ignoring any inside strings
+fn find_depth_exceeded(s: &str, max_depth: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, b) in s.bytes().enumerate() {
+        if in_string {
+            match b {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match b {
+            b'"' => in_string = true,
+            b'[' | b'{' => {
+                depth += 1;
+                if depth > max_depth {
+                    return Some(i);
+                }
+            }
+            b']' | b'}' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Reads the JSON file at `path` and fails with a data error naming any
+/// fields present in the file that are not consumed by `T`
 #[allow(unused)]
-pub fn read_json_file<T>(path: &Path) -> StdResult<T, JsonError>
+pub fn read_json_file_deny_unknown<T>(path: impl AsRef<Path>) -> StdResult<T, JsonError>
 where
     T: DeserializeOwned,
 {
-    let s = read_text_file(path).map_err(JsonError::other)?;
-    let value = serde_json::from_str::<T>(&s).map_err(|e| JsonError::convert(&e, path))?;
+    let path = path.as_ref();
+    let s = read_text_file_no_bom(path).map_err(JsonError::other)?;
+    let mut unknown = Vec::new();
+    let mut deserializer = serde_json::Deserializer::from_str(&s);
+    let value = serde_ignored::deserialize(&mut deserializer, |p| unknown.push(p.to_string()))
+        .map_err(|e| JsonError::convert(&e, path, Some(&s)))?;
+    deserializer
+        .end()
+        .map_err(|e| JsonError::convert(&e, path, Some(&s)))?;
+
+    if !unknown.is_empty() {
+        return Err(JsonError(JsonErrorImpl::Data {
+            message: format!("Unknown fields: {}", unknown.join(", ")),
+            path: path.to_path_buf(),
+            line_col: None,
+            excerpt: None,
+        }));
+    }
+
     Ok(value)
 }
 
+/// Reads the JSON array at `path` into a `Vec`, converting each element
+/// separately so that a data error reports the index of the failing element
+#[allow(unused)]
+pub fn read_json_vec<T>(path: impl AsRef<Path>) -> StdResult<Vec<T>, JsonError>
+where
+    T: DeserializeOwned,
+{
+    let path = path.as_ref();
+    let s = read_text_file_no_bom(path).map_err(JsonError::other)?;
+    let values = serde_json::from_str::<Vec<Value>>(&s)
+        .map_err(|e| JsonError::convert(&e, path, Some(&s)))?;
+    values
+        .into_iter()
+        .enumerate()
+        .map(|(i, value)| {
+            serde_json::from_value::<T>(value).map_err(|e| {
+                JsonError(JsonErrorImpl::Data {
+                    message: format!("Element {i}: {e}"),
+                    path: path.to_path_buf(),
+                    line_col: None,
+                    excerpt: None,
+                })
+            })
+        })
+        .collect()
+}
+
+/// Writes `value` to `path` as compact JSON followed by a single newline
+#[allow(unused)]
+pub fn write_json_file_compact<T>(
+    path: impl AsRef<Path>,
+    value: &T,
+    overwrite: bool,
+) -> StdResult<(), JsonError>
+where
+    T: Serialize,
+{
+    let path = path.as_ref();
+    let mut s = serde_json::to_string(value).map_err(JsonError::other)?;
+    s.push('\n');
+    safe_write_file(path, s, overwrite).map_err(JsonError::other)?;
+    Ok(())
+}
+
+/// Writes `items` to `path` as a compact JSON array, serializing each item
+/// as it is produced so the collection is never held in memory
+///
+/// The array is written to a temporary file which replaces `path` only
+/// once it is complete, so a failure leaves any existing file untouched
+#[allow(unused)]
+pub fn write_json_array<T, I>(
+    path: impl AsRef<Path>,
+    items: I,
+    overwrite: bool,
+) -> StdResult<(), JsonError>
+where
+    T: Serialize,
+    I: IntoIterator<Item = T>,
+{
+    const BUFFER_SIZE: usize = 8192;
+
+    let path = path.as_ref();
+    let mut writer = SafeWriter::new(path, overwrite).map_err(JsonError::other)?;
+    let mut buffer = Vec::with_capacity(BUFFER_SIZE);
+    buffer.push(b'[');
+    for (i, item) in items.into_iter().enumerate() {
+        if i > 0 {
+            buffer.push(b',');
+        }
+        serde_json::to_writer(&mut buffer, &item)
+            .map_err(|e| JsonError::convert(&e, path, None))?;
+        if buffer.len() >= BUFFER_SIZE {
+            writer.write_all(&buffer).map_err(JsonError::other)?;
+            buffer.clear();
+        }
+    }
+    buffer.extend_from_slice(b"]\n");
+    writer.write_all(&buffer).map_err(JsonError::other)?;
+    writer.commit().map_err(JsonError::other)?;
+    Ok(())
+}
+
+/// Writes `value` to `path` as pretty-printed JSON, backing up any
+/// existing file and replacing it atomically after syncing to disk, and
+/// then reads the file back to check that it matches `value`
+#[allow(unused)]
+pub fn write_json_file_durable<T>(
+    path: impl AsRef<Path>,
+    value: &T,
+    overwrite: bool,
+) -> StdResult<(), JsonError>
+where
+    T: Serialize + DeserializeOwned + PartialEq,
+{
+    let path = path.as_ref();
+    let s = serde_json::to_string_pretty(value).map_err(JsonError::other)?;
+    WriteOptions::new()
+        .overwrite(overwrite)
+        .atomic(true)
+        .backup(true)
+        .write(path, s)
+        .map_err(JsonError::other)?;
+
+    if read_json_file::<T>(path)? != *value {
+        return Err(JsonError(JsonErrorImpl::Data {
+            message: String::from("Value read back does not match value written"),
+            path: path.to_path_buf(),
+            line_col: None,
+            excerpt: None,
+        }));
+    }
+
+    Ok(())
+}
+
+/// Appends `value` to `path` as a single line of compact JSON followed by
+/// a newline, creating the file if it does not exist
+///
+/// The file is opened in append mode so concurrent appenders do not
+/// overwrite each other, although long lines may interleave
+#[allow(unused)]
+pub fn append_ndjson<T>(path: impl AsRef<Path>, value: &T) -> StdResult<(), JsonError>
+where
+    T: Serialize,
+{
+    let path = path.as_ref();
+    let mut s = serde_json::to_string(value).map_err(JsonError::other)?;
+    s.push('\n');
+    append_file(path, s).map_err(JsonError::other)?;
+    Ok(())
+}
+
+/// Checks that `value` is an object containing each of `keys`
+#[allow(unused)]
+pub fn require_keys(value: &Value, keys: &[&str]) -> StdResult<(), JsonError> {
+    let Some(obj) = value.as_object() else {
+        return Err(JsonError(JsonErrorImpl::InvalidData {
+            message: String::from("Value is not a JSON object"),
+        }));
+    };
+
+    let missing = keys
+        .iter()
+        .filter(|k| !obj.contains_key(**k))
+        .copied()
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        return Err(JsonError(JsonErrorImpl::InvalidData {
+            message: format!("Missing required keys: {}", missing.join(", ")),
+        }));
+    }
+
+    Ok(())
+}
+
+/// Returns a description of each difference between `a` and `b`,
+/// identifying the location of each using a JSON pointer
+#[allow(unused)]
+#[must_use]
+pub fn json_diff(a: &Value, b: &Value) -> Vec<String> {
+    let mut diffs = Vec::new();
+    json_diff_inner(a, b, "", &mut diffs);
+    diffs
+}
+
+fn json_diff_inner(a: &Value, b: &Value, pointer: &str, diffs: &mut Vec<String>) {
+    match (a, b) {
+        (Value::Object(a), Value::Object(b)) => {
+            for (k, v) in a {
+                let child = format!("{pointer}/{}", escape_pointer_token(k));
+                match b.get(k) {
+                    Some(other) => json_diff_inner(v, other, &child, diffs),
+                    None => diffs.push(format!("{child}: removed {v}")),
+                }
+            }
+            for (k, v) in b {
+                if !a.contains_key(k) {
+                    let child = format!("{pointer}/{}", escape_pointer_token(k));
+                    diffs.push(format!("{child}: added {v}"));
+                }
+            }
+        }
+        (Value::Array(a), Value::Array(b)) => {
+            for (i, v) in a.iter().enumerate() {
+                let child = format!("{pointer}/{i}");
+                match b.get(i) {
+                    Some(other) => json_diff_inner(v, other, &child, diffs),
+                    None => diffs.push(format!("{child}: removed {v}")),
+                }
+            }
+            for (i, v) in b.iter().enumerate().skip(a.len()) {
+                diffs.push(format!("{pointer}/{i}: added {v}"));
+            }
+        }
+        _ => {
+            if a != b {
+                diffs.push(format!("{pointer}: changed {a} to {b}"));
+            }
+        }
+    }
+}
+
+fn escape_pointer_token(s: &str) -> String {
+    s.replace('~', "~0").replace('/', "~1")
+}
+
+/// Applies the JSON Patch (RFC 6902) operations in `ops` to `value`
+///
+/// Either all operations are applied or, if any operation fails, `value`
+/// is left unchanged
+#[allow(unused)]
+pub fn apply_json_patch(value: &mut Value, ops: &Value) -> StdResult<(), JsonError> {
+    let Some(ops) = ops.as_array() else {
+        return Err(invalid_patch("Patch is not a JSON array"));
+    };
+
+    let mut result = value.clone();
+    for (i, op) in ops.iter().enumerate() {
+        apply_json_patch_op(&mut result, op)
+            .map_err(|message| invalid_patch(&format!("Operation {i} failed: {message}")))?;
+    }
+
+    *value = result;
+    Ok(())
+}
+
+fn invalid_patch(message: &str) -> JsonError {
+    JsonError(JsonErrorImpl::InvalidData {
+        message: message.to_string(),
+    })
+}
+
+fn apply_json_patch_op(value: &mut Value, op: &Value) -> StdResult<(), String> {
+    let member = |name: &str| {
+        op.get(name)
+            .ok_or_else(|| format!("missing \"{name}\" member"))
+    };
+    let pointer_member = |name: &str| {
+        member(name)?
+            .as_str()
+            .ok_or_else(|| format!("\"{name}\" member is not a string"))
+    };
+
+    let path = pointer_member("path")?;
+    match pointer_member("op")? {
+        "add" => patch_add(value, path, member("value")?.clone()),
+        "remove" => patch_remove(value, path).map(|_| ()),
+        "replace" => {
+            let target = value
+                .pointer_mut(path)
+                .ok_or_else(|| format!("path {path} does not exist"))?;
+            *target = member("value")?.clone();
+            Ok(())
+        }
+        "move" => {
+            let from = pointer_member("from")?;
+            if path.starts_with(from) && path[from.len()..].starts_with('/') {
+                return Err(format!("cannot move {from} into its own child {path}"));
+            }
+            let v = patch_remove(value, from)?;
+            patch_add(value, path, v)
+        }
+        "copy" => {
+            let from = pointer_member("from")?;
+            let v = value
+                .pointer(from)
+                .ok_or_else(|| format!("path {from} does not exist"))?
+                .clone();
+            patch_add(value, path, v)
+        }
+        "test" => {
+            let expected = member("value")?;
+            match value.pointer(path) {
+                Some(actual) if actual == expected => Ok(()),
+                Some(actual) => Err(format!(
+                    "test failed at {path}: expected {expected}, found {actual}"
+                )),
+                None => Err(format!("test failed: path {path} does not exist")),
+            }
+        }
+        other => Err(format!("unsupported operation \"{other}\"")),
+    }
+}
+
+// Splits a JSON pointer into the pointer to its parent and its unescaped
+// last token, or returns None for the root pointer
+fn split_pointer(path: &str) -> StdResult<Option<(&str, String)>, String> {
+    if path.is_empty() {
+        return Ok(None);
+    }
+    if !path.starts_with('/') {
+        return Err(format!("invalid JSON pointer {path}"));
+    }
+    let i = path.rfind('/').unwrap_or_default();
+    let token = path[i + 1..].replace("~1", "/").replace("~0", "~");
+    Ok(Some((&path[..i], token)))
+}
+
+fn parse_array_index(token: &str, len: usize, path: &str) -> StdResult<usize, String> {
+    if token.is_empty() || (token.len() > 1 && token.starts_with('0')) {
+        return Err(format!("invalid array index in {path}"));
+    }
+    match token.parse::<usize>() {
+        Ok(index) if index <= len => Ok(index),
+        _ => Err(format!("array index in {path} is out of bounds")),
+    }
+}
+
+fn patch_add(value: &mut Value, path: &str, v: Value) -> StdResult<(), String> {
+    let Some((parent_path, token)) = split_pointer(path)? else {
+        *value = v;
+        return Ok(());
+    };
+
+    match value.pointer_mut(parent_path) {
+        Some(Value::Object(obj)) => {
+            obj.insert(token, v);
+            Ok(())
+        }
+        Some(Value::Array(arr)) => {
+            let index = if token == "-" {
+                arr.len()
+            } else {
+                parse_array_index(&token, arr.len(), path)?
+            };
+            arr.insert(index, v);
+            Ok(())
+        }
+        Some(_) => Err(format!("parent of {path} is not a container")),
+        None => Err(format!("parent of {path} does not exist")),
+    }
+}
+
+fn patch_remove(value: &mut Value, path: &str) -> StdResult<Value, String> {
+    let Some((parent_path, token)) = split_pointer(path)? else {
+        return Err(String::from("cannot remove the root"));
+    };
+
+    match value.pointer_mut(parent_path) {
+        Some(Value::Object(obj)) => obj
+            .remove(&token)
+            .ok_or_else(|| format!("path {path} does not exist")),
+        Some(Value::Array(arr)) => {
+            let index = parse_array_index(&token, arr.len(), path)?;
+            if index == arr.len() {
+                return Err(format!("array index in {path} is out of bounds"));
+            }
+            Ok(arr.remove(index))
+        }
+        _ => Err(format!("path {path} does not exist")),
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{read_json_file, JsonErrorKind};
+    use super::{
+        append_ndjson, apply_json_patch, json_diff, read_json_file, read_json_file_bounded,
+        read_json_file_deny_unknown, read_json_vec, require_keys, write_json_array,
+        write_json_file_compact, write_json_file_durable, JsonErrorKind,
+    };
     use anyhow::Result;
+    use serde::Deserialize;
     use serde_json::{json, Value};
-    use std::fs::write;
+    use std::fs::{read_dir, read_to_string, write};
     use tempdir::TempDir;
 
     #[test]
@@ -173,6 +820,25 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_read_json_file_bom_succeeds() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let bom_path = temp_dir.path().join("bom.json");
+        let no_bom_path = temp_dir.path().join("no-bom.json");
+        write(&bom_path, b"\xef\xbb\xbf{\"message\": \"hello-world\"}")?;
+        write(&no_bom_path, "{\"message\": \"hello-world\"}")?;
+
+        // Act
+        let bom_value = read_json_file::<Value>(&bom_path)?;
+        let no_bom_value = read_json_file::<Value>(&no_bom_path)?;
+
+        // Assert
+        assert_eq!(no_bom_value, bom_value);
+        assert_eq!(json!({"message": "hello-world"}), bom_value);
+        Ok(())
+    }
+
     #[test]
     fn test_read_json_file_invalid_fails() -> Result<()> {
         // Arrange
@@ -197,4 +863,638 @@ mod tests {
         assert!(message.contains(path.to_str().expect("must be valid string")));
         Ok(())
     }
+
+    #[cfg(feature = "flate2")]
+    #[test]
+    fn test_read_json_gz_file_succeeds() -> Result<()> {
+        use super::read_json_gz_file;
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+        use std::io::Write;
+
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.json.gz");
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"{\"message\": \"hello-world\"}")?;
+        write(&path, encoder.finish()?)?;
+
+        // Act
+        let value = read_json_gz_file::<Value>(&path)?;
+
+        // Assert
+        assert_eq!(json!({"message": "hello-world"}), value);
+        Ok(())
+    }
+
+    #[cfg(feature = "flate2")]
+    #[test]
+    fn test_read_json_gz_file_invalid_fails() -> Result<()> {
+        use super::read_json_gz_file;
+
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.json.gz");
+        write(&path, "{\"message\": \"hello-world\"}")?;
+
+        // Act
+        let Err(e) = read_json_gz_file::<Value>(&path) else {
+            panic!("read_json_gz_file must fail")
+        };
+
+        // Assert
+        assert_eq!(JsonErrorKind::Io, e.kind());
+        let message = format!("{e}");
+        assert!(message.contains(path.to_str().expect("must be valid string")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_json_error_eq() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.json");
+        let other_path = temp_dir.path().join("other.json");
+        write(&path, "xxx")?;
+        write(&other_path, "xxx")?;
+
+        // Act
+        let Err(e0) = read_json_file::<Value>(&path) else {
+            panic!("read_json_file must fail")
+        };
+        let Err(e1) = read_json_file::<Value>(&path) else {
+            panic!("read_json_file must fail")
+        };
+        let Err(e2) = read_json_file::<Value>(&other_path) else {
+            panic!("read_json_file must fail")
+        };
+
+        // Assert
+        assert_eq!(e0, e1);
+        assert_ne!(e0, e2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_json_file_bounded_succeeds() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.json");
+        write(&path, format!("{}{}", "[".repeat(50), "]".repeat(50)))?;
+
+        // Act
+        let value = read_json_file_bounded::<Value>(&path, 100)?;
+
+        // Assert
+        assert!(value.is_array());
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_json_file_bounded_too_deep_fails() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.json");
+        write(
+            &path,
+            format!(
+                "{{\"message\": \"[[[[\", \"value\": {}{}}}",
+                "[".repeat(50),
+                "]".repeat(50)
+            ),
+        )?;
+
+        // Act
+        let Err(e) = read_json_file_bounded::<Value>(&path, 10) else {
+            panic!("read_json_file_bounded must fail")
+        };
+
+        // Assert
+        assert_eq!(JsonErrorKind::Syntax, e.kind());
+        let message = format!("{e}");
+        assert!(message.contains("recursion limit of 10 exceeded"));
+        assert!(message.contains(path.to_str().expect("must be valid string")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_json_file_bounded_clamps_max_depth() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path0 = temp_dir.path().join("file0.json");
+        let path1 = temp_dir.path().join("file1.json");
+        write(&path0, format!("{}{}", "[".repeat(127), "]".repeat(127)))?;
+        write(&path1, format!("{}{}", "[".repeat(128), "]".repeat(128)))?;
+
+        // Act
+        let value = read_json_file_bounded::<Value>(&path0, 1000)?;
+        let Err(e) = read_json_file_bounded::<Value>(&path1, 1000) else {
+            panic!("read_json_file_bounded must fail")
+        };
+
+        // Assert
+        assert!(value.is_array());
+        assert!(format!("{e}").contains("recursion limit of 127 exceeded"));
+        Ok(())
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Message {
+        message: String,
+    }
+
+    #[test]
+    fn test_read_json_vec_succeeds() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.json");
+        write(&path, "[1, 2, 3]")?;
+
+        // Act
+        let value = read_json_vec::<i32>(&path)?;
+
+        // Assert
+        assert_eq!(vec![1, 2, 3], value);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_json_vec_reports_index() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.json");
+        write(&path, "[1, 2, 3, \"four\", 5]")?;
+
+        // Act
+        let Err(e) = read_json_vec::<i32>(&path) else {
+            panic!("read_json_vec must fail")
+        };
+
+        // Assert
+        assert_eq!(JsonErrorKind::Data, e.kind());
+        assert!(format!("{e}").starts_with("Element 3: "));
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_json_file_deny_unknown_succeeds() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.json");
+        write(&path, "{\"message\": \"hello-world\"}")?;
+
+        // Act
+        let value = read_json_file_deny_unknown::<Message>(&path)?;
+
+        // Assert
+        assert_eq!(
+            Message {
+                message: String::from("hello-world")
+            },
+            value
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_json_file_deny_unknown_fails() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.json");
+        write(
+            &path,
+            "{\"message\": \"hello-world\", \"mesage\": \"typo\"}",
+        )?;
+
+        // Act
+        let Err(e) = read_json_file_deny_unknown::<Message>(&path) else {
+            panic!("read_json_file_deny_unknown must fail")
+        };
+
+        // Assert
+        assert_eq!(JsonErrorKind::Data, e.kind());
+        assert!(e.is_data());
+        let message = format!("{e}");
+        assert!(message.contains("mesage"));
+        assert!(message.contains(path.to_str().expect("must be valid string")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_require_keys_succeeds() {
+        // Arrange
+        let value = json!({"aaa": 1, "bbb": 2, "ccc": 3});
+
+        // Act
+        let result = require_keys(&value, &["aaa", "ccc"]);
+
+        // Assert
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_require_keys_missing_fails() {
+        // Arrange
+        let value = json!({"aaa": 1});
+
+        // Act
+        let Err(e) = require_keys(&value, &["aaa", "bbb", "ccc"]) else {
+            panic!("require_keys must fail")
+        };
+
+        // Assert
+        assert_eq!(JsonErrorKind::Data, e.kind());
+        assert!(e.is_data());
+        let message = format!("{e}");
+        assert!(message.contains("bbb, ccc"));
+        assert!(!message.contains("aaa"));
+    }
+
+    #[test]
+    fn test_json_diff_equal() {
+        // Arrange
+        let value = json!({"aaa": [1, 2, {"bbb": true}]});
+
+        // Act
+        let diffs = json_diff(&value, &value.clone());
+
+        // Assert
+        assert!(diffs.is_empty());
+    }
+
+    #[test]
+    fn test_json_diff_added() {
+        // Arrange
+        let a = json!({"aaa": {"bbb": 1}, "ccc": [1]});
+        let b = json!({"aaa": {"bbb": 1, "a/b": 2}, "ccc": [1, 2]});
+
+        // Act
+        let diffs = json_diff(&a, &b);
+
+        // Assert
+        assert_eq!(vec!["/aaa/a~1b: added 2", "/ccc/1: added 2"], diffs);
+    }
+
+    #[test]
+    fn test_json_diff_removed() {
+        // Arrange
+        let a = json!({"aaa": {"bbb": 1, "ccc": "x"}});
+        let b = json!({"aaa": {"bbb": 1}});
+
+        // Act
+        let diffs = json_diff(&a, &b);
+
+        // Assert
+        assert_eq!(vec!["/aaa/ccc: removed \"x\""], diffs);
+    }
+
+    #[test]
+    fn test_json_diff_changed() {
+        // Arrange
+        let a = json!({"server": {"port": 8080, "hosts": ["a", "b"]}});
+        let b = json!({"server": {"port": 9090, "hosts": ["a", "c"]}});
+
+        // Act
+        let diffs = json_diff(&a, &b);
+
+        // Assert
+        assert_eq!(
+            vec![
+                "/server/hosts/1: changed \"b\" to \"c\"",
+                "/server/port: changed 8080 to 9090"
+            ],
+            diffs
+        );
+    }
+
+    #[test]
+    fn test_display_pretty() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.json");
+        write(&path, "{\n  \"message\": xxx\n}")?;
+        let Err(e) = read_json_file::<Value>(&path) else {
+            panic!("read_json_file must fail")
+        };
+
+        // Act
+        let output = e.display_pretty(false);
+
+        // Assert
+        assert!(output.starts_with("error: "));
+        assert!(output.contains(path.to_str().expect("must be valid string")));
+        assert!(output.lines().any(|line| line.trim_end().ends_with('^')));
+        assert!(!output.contains('\x1b'));
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_json_patch_add() -> Result<()> {
+        // Arrange
+        let mut value = json!({ "aaa": [1, 3] });
+        let ops = json!([
+            { "op": "add", "path": "/bbb", "value": "hello" },
+            { "op": "add", "path": "/aaa/1", "value": 2 },
+            { "op": "add", "path": "/aaa/-", "value": 4 }
+        ]);
+
+        // Act
+        apply_json_patch(&mut value, &ops)?;
+
+        // Assert
+        assert_eq!(json!({ "aaa": [1, 2, 3, 4], "bbb": "hello" }), value);
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_json_patch_remove() -> Result<()> {
+        // Arrange
+        let mut value = json!({ "aaa": [1, 2, 3], "b/b": true });
+        let ops = json!([
+            { "op": "remove", "path": "/aaa/1" },
+            { "op": "remove", "path": "/b~1b" }
+        ]);
+
+        // Act
+        apply_json_patch(&mut value, &ops)?;
+
+        // Assert
+        assert_eq!(json!({ "aaa": [1, 3] }), value);
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_json_patch_replace() -> Result<()> {
+        // Arrange
+        let mut value = json!({ "aaa": { "bbb": 1 } });
+        let ops = json!([
+            { "op": "replace", "path": "/aaa/bbb", "value": 2 },
+            { "op": "copy", "from": "/aaa", "path": "/ccc" },
+            { "op": "move", "from": "/aaa/bbb", "path": "/ddd" }
+        ]);
+
+        // Act
+        apply_json_patch(&mut value, &ops)?;
+
+        // Assert
+        assert_eq!(json!({ "aaa": {}, "ccc": { "bbb": 2 }, "ddd": 2 }), value);
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_json_patch_test_fails() {
+        // Arrange
+        let mut value = json!({ "aaa": 1 });
+        let ops = json!([
+            { "op": "replace", "path": "/aaa", "value": 2 },
+            { "op": "test", "path": "/aaa", "value": 3 }
+        ]);
+
+        // Act
+        let Err(e) = apply_json_patch(&mut value, &ops) else {
+            panic!("apply_json_patch must fail")
+        };
+
+        // Assert
+        assert_eq!(JsonErrorKind::Data, e.kind());
+        assert!(format!("{e}").contains("test failed at /aaa"));
+        assert_eq!(json!({ "aaa": 1 }), value);
+    }
+
+    #[test]
+    fn test_apply_json_patch_invalid_path_fails() {
+        // Arrange
+        let mut value = json!({ "aaa": 1 });
+        let ops = json!([{ "op": "remove", "path": "/bbb" }]);
+
+        // Act
+        let Err(e) = apply_json_patch(&mut value, &ops) else {
+            panic!("apply_json_patch must fail")
+        };
+
+        // Assert
+        assert_eq!(JsonErrorKind::Data, e.kind());
+        assert!(format!("{e}").contains("/bbb does not exist"));
+    }
+
+    #[test]
+    fn test_write_json_file_compact_succeeds() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.json");
+        let value = json!({ "aaa": [1, 2, 3], "bbb": { "ccc": "hello world" } });
+
+        // Act
+        write_json_file_compact(&path, &value, false)?;
+
+        // Assert
+        let s = read_to_string(&path)?;
+        assert_eq!("{\"aaa\":[1,2,3],\"bbb\":{\"ccc\":\"hello world\"}}\n", s);
+        assert_eq!(value, serde_json::from_str::<Value>(&s)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_json_array_succeeds() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.json");
+        let items = (0..1000).map(|i| json!({ "id": i, "name": format!("item{i}") }));
+
+        // Act
+        write_json_array(&path, items.clone(), false)?;
+
+        // Assert
+        let values = read_json_file::<Vec<Value>>(&path)?;
+        assert_eq!(items.collect::<Vec<_>>(), values);
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_json_array_empty() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.json");
+
+        // Act
+        write_json_array(&path, Vec::<Value>::new(), false)?;
+
+        // Assert
+        assert_eq!("[]\n", read_to_string(&path)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_json_array_item_fails_keeps_existing() -> Result<()> {
+        use serde::ser::Error as SerError;
+        use serde::{Serialize, Serializer};
+
+        struct Item(Option<i32>);
+
+        impl Serialize for Item {
+            fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+            where
+                S: Serializer,
+            {
+                match self.0 {
+                    Some(value) => serializer.serialize_i32(value),
+                    None => Err(S::Error::custom("cannot serialize item")),
+                }
+            }
+        }
+
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.json");
+        write(&path, "[1]\n")?;
+        let items = (0..10_000).map(|i| Item((i < 5000).then_some(i)));
+
+        // Act
+        let Err(e) = write_json_array(&path, items, true) else {
+            panic!("write_json_array must fail")
+        };
+
+        // Assert
+        assert!(e.is_data());
+        assert_eq!("[1]\n", read_to_string(&path)?);
+        assert_eq!(1, read_dir(temp_dir.path())?.count());
+        Ok(())
+    }
+
+    #[test]
+    fn test_append_ndjson_succeeds() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("events.ndjson");
+        let records = [
+            json!({ "id": 0, "message": "aaa" }),
+            json!({ "id": 1, "message": "bbb\nccc" }),
+            json!({ "id": 2, "items": [1, 2, 3] }),
+        ];
+
+        // Act
+        for record in &records {
+            append_ndjson(&path, record)?;
+        }
+
+        // Assert
+        let s = read_to_string(&path)?;
+        assert!(s.ends_with('\n'));
+        let values = s
+            .lines()
+            .map(serde_json::from_str::<Value>)
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(records.to_vec(), values);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_json_file_as_ref_path() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.json");
+        write(&path, "{\"message\": \"hello-world\"}")?;
+        let s = path.to_str().expect("must be valid string");
+
+        // Act
+        let from_str = read_json_file::<Value>(s)?;
+        let from_string = read_json_file::<Value>(String::from(s))?;
+        let from_path_buf = read_json_file::<Value>(path.clone())?;
+
+        // Assert
+        let expected = json!({"message": "hello-world"});
+        assert_eq!(expected, from_str);
+        assert_eq!(expected, from_string);
+        assert_eq!(expected, from_path_buf);
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_json_file_durable_succeeds() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.json");
+        write(&path, "{}")?;
+        let value = json!({"message": "hello-world", "items": [1, 2, 3]});
+
+        // Act
+        write_json_file_durable(&path, &value, true)?;
+
+        // Assert
+        assert_eq!(value, read_json_file::<Value>(&path)?);
+        assert_eq!(2, read_dir(temp_dir.path())?.count());
+        Ok(())
+    }
+
+    #[test]
+    fn test_json_error_has_position() -> Result<()> {
+        use crate::formats::HasPosition;
+
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.json");
+        write(&path, "{\n  \"message\": xyz\n}")?;
+
+        // Act
+        let Err(e) = read_json_file::<Value>(&path) else {
+            panic!("read_json_file must fail")
+        };
+
+        // Assert
+        let (line, _) = e.line_col().expect("must have line and column");
+        assert_eq!(2, line);
+        let span = e.byte_span().expect("must have span");
+        assert!(span.start >= "{\n".len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_json_error_retains_excerpt_only() -> Result<()> {
+        use crate::formats::HasPosition;
+
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.json");
+        let s = format!(
+            "[\n{}  xyz\n{}]",
+            "  1,\n".repeat(10_000),
+            "  1,\n".repeat(10_000)
+        );
+        write(&path, &s)?;
+
+        // Act
+        let Err(e) = read_json_file::<Value>(&path) else {
+            panic!("read_json_file must fail")
+        };
+
+        // Assert
+        assert!(format!("{e:?}").len() < 1000);
+        assert_eq!(Some((10_002, 3)), e.line_col());
+        let offset = s.find("xyz").expect("must be found");
+        assert_eq!(Some(offset..offset + 1), e.byte_span());
+        assert!(e.display_pretty(false).contains("10002 |   xyz"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_json_error_has_position_multi_byte() -> Result<()> {
+        use crate::formats::HasPosition;
+
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.json");
+        let s = "[\"\u{e9}\u{e9}\u{e9}\", x]";
+        write(&path, s)?;
+
+        // Act
+        let Err(e) = read_json_file::<Value>(&path) else {
+            panic!("read_json_file must fail")
+        };
+
+        // Assert
+        assert_eq!(Some((1, 9)), e.line_col());
+        let offset = s.find('x').expect("must be found");
+        assert_eq!(Some(offset..offset + 1), e.byte_span());
+        Ok(())
+    }
 }
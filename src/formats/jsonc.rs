@@ -0,0 +1,122 @@
+// Copyright (c) 2023 Richard Cook
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the
+// "Software"), to deal in the Software without restriction, including
+// without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to
+// permit persons to whom the Software is furnished to do so, subject to
+// the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE
+// LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION
+// WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+//
+use super::json::JsonError;
+use crate::fs::{read_text_file_no_bom, safe_write_file};
+use jsonc_parser::cst::CstRootNode;
+use jsonc_parser::ParseOptions;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::path::Path;
+use std::result::Result as StdResult;
+
+/// A JSONC document that preserves comments and formatting across edits
+/// made through its concrete syntax tree
+pub struct JsoncDocument(CstRootNode);
+
+impl JsoncDocument {
+    /// Returns the root of the concrete syntax tree for editing
+    #[allow(unused)]
+    #[must_use]
+    pub const fn root(&self) -> &CstRootNode {
+        &self.0
+    }
+
+    /// Writes the document, including any edits, to `path`
+    #[allow(unused)]
+    pub fn write(&self, path: impl AsRef<Path>, overwrite: bool) -> StdResult<(), JsonError> {
+        let path = path.as_ref();
+        safe_write_file(path, self.0.to_string(), overwrite).map_err(JsonError::other)?;
+        Ok(())
+    }
+}
+
+impl Display for JsoncDocument {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[allow(unused)]
+pub fn read_jsonc_preserving(path: impl AsRef<Path>) -> StdResult<JsoncDocument, JsonError> {
+    let path = path.as_ref();
+    let s = read_text_file_no_bom(path).map_err(JsonError::other)?;
+    let root = CstRootNode::parse(&s, &ParseOptions::default())
+        .map_err(|e| JsonError::syntax(e.to_string(), path))?;
+    Ok(JsoncDocument(root))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::read_jsonc_preserving;
+    use crate::JsonErrorKind;
+    use anyhow::Result;
+    use jsonc_parser::json;
+    use std::fs::{read_to_string, write};
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_read_jsonc_preserving_edit_keeps_comments() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.jsonc");
+        let output_path = temp_dir.path().join("output.jsonc");
+        write(
+            &path,
+            "{\n  // line comment\n  \"aaa\": 1,\n  /* block comment */\n  \"bbb\": 2\n}\n",
+        )?;
+        let doc = read_jsonc_preserving(&path)?;
+
+        // Act
+        doc.root()
+            .object_value()
+            .expect("must be object")
+            .get("aaa")
+            .expect("must exist")
+            .set_value(json!(100));
+        doc.write(&output_path, false)?;
+
+        // Assert
+        assert_eq!(
+            "{\n  // line comment\n  \"aaa\": 100,\n  /* block comment */\n  \"bbb\": 2\n}\n",
+            read_to_string(&output_path)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_jsonc_preserving_invalid_fails() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.jsonc");
+        write(&path, "{\n  // comment\n  \"aaa\": xxx\n}\n")?;
+
+        // Act
+        let Err(e) = read_jsonc_preserving(&path) else {
+            panic!("read_jsonc_preserving must fail")
+        };
+
+        // Assert
+        assert_eq!(JsonErrorKind::Syntax, e.kind());
+        let message = format!("{e}");
+        assert!(message.contains(path.to_str().expect("must be valid string")));
+        Ok(())
+    }
+}
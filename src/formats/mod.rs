@@ -19,10 +19,54 @@
 // OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION
 // WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
 //
+mod config;
+#[cfg(feature = "csv")]
+mod csv;
+mod diagnostic;
+mod env;
+mod format;
+#[cfg(feature = "ini")]
+mod ini;
 mod json;
+#[cfg(feature = "jsonc")]
+mod jsonc;
+#[cfg(feature = "msgpack")]
+mod msgpack;
+mod position;
+#[cfg(feature = "ron")]
+mod ron;
 mod toml;
+mod value;
 mod yaml;
 
-pub use self::json::{read_json_file, JsonError, JsonErrorKind};
-pub use self::toml::{read_toml_file, read_toml_file_edit, TomlError, TomlErrorKind};
-pub use self::yaml::{read_yaml_file, YamlError, YamlErrorKind};
+pub use self::config::{read_layered_with_provenance, ConfigError, ConfigErrorKind};
+#[cfg(feature = "csv")]
+pub use self::csv::{read_csv_file, CsvError, CsvErrorKind};
+pub use self::env::read_env_file;
+pub use self::format::{load, Format, FormatError, FormatErrorKind, Json, Toml, Yaml};
+#[cfg(feature = "ini")]
+pub use self::ini::{read_ini_file, IniError, IniErrorKind};
+#[cfg(feature = "flate2")]
+pub use self::json::read_json_gz_file;
+pub use self::json::{
+    append_ndjson, apply_json_patch, json_diff, read_json_file, read_json_file_bounded,
+    read_json_file_deny_unknown, read_json_vec, require_keys, write_json_array,
+    write_json_file_compact, write_json_file_durable, JsonError, JsonErrorKind,
+};
+#[cfg(feature = "jsonc")]
+pub use self::jsonc::{read_jsonc_preserving, JsoncDocument};
+#[cfg(feature = "msgpack")]
+pub use self::msgpack::{read_msgpack_file, write_msgpack_file, MsgpackError, MsgpackErrorKind};
+pub use self::position::{offset_to_line_col, HasPosition};
+#[cfg(feature = "ron")]
+pub use self::ron::{read_ron_file, RonError, RonErrorKind};
+pub use self::toml::{
+    doc_get_str, doc_remove_path, merge_toml, read_layered_toml, read_toml_array_of_tables,
+    read_toml_file, read_toml_file_both, read_toml_file_edit, toml_datetime_to_chrono,
+    toml_set_in_file, write_toml_file_sorted, TomlError, TomlErrorKind,
+};
+pub use self::value::value_get_path;
+pub use self::yaml::{
+    read_yaml_dir, read_yaml_file, read_yaml_tagged, write_yaml_documents, write_yaml_file_styled,
+    YamlError, YamlErrorKind,
+};
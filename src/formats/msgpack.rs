@@ -0,0 +1,234 @@
+// Copyright (c) 2023 Richard Cook
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the
+// "Software"), to deal in the Software without restriction, including
+// without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to
+// permit persons to whom the Software is furnished to do so, subject to
+// the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE
+// LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION
+// WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+//
+use crate::error::HasOtherError;
+use crate::fs::{read_bytes, safe_write_file};
+use anyhow::Error as AnyhowError;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::any::Any;
+use std::error::Error as StdError;
+use std::fmt::{Debug, Display};
+use std::path::{Path, PathBuf};
+use std::result::Result as StdResult;
+use thiserror::Error;
+
+#[allow(unused)]
+#[derive(Debug, PartialEq)]
+#[non_exhaustive]
+pub enum MsgpackErrorKind {
+    Decode,
+    Encode,
+    Other,
+}
+
+#[derive(Debug, Error, PartialEq)]
+#[error(transparent)]
+pub struct MsgpackError(#[from] MsgpackErrorImpl);
+
+impl MsgpackError {
+    #[allow(unused)]
+    #[must_use]
+    pub const fn kind(&self) -> MsgpackErrorKind {
+        match self.0 {
+            MsgpackErrorImpl::Decode { .. } => MsgpackErrorKind::Decode,
+            MsgpackErrorImpl::Encode { .. } => MsgpackErrorKind::Encode,
+            MsgpackErrorImpl::Other(_) => MsgpackErrorKind::Other,
+        }
+    }
+
+    /// Returns whether the error is of the given kind
+    #[allow(unused)]
+    #[allow(clippy::needless_pass_by_value)]
+    #[must_use]
+    pub fn is_kind(&self, kind: MsgpackErrorKind) -> bool {
+        self.kind() == kind
+    }
+
+    #[allow(unused)]
+    #[must_use]
+    pub fn is_decode(&self) -> bool {
+        self.kind() == MsgpackErrorKind::Decode
+    }
+
+    #[allow(unused)]
+    #[must_use]
+    pub fn is_encode(&self) -> bool {
+        self.kind() == MsgpackErrorKind::Encode
+    }
+
+    #[allow(unused)]
+    #[must_use]
+    pub fn is_other(&self) -> bool {
+        self.kind() == MsgpackErrorKind::Other
+    }
+
+    fn other<E>(e: E) -> Self
+    where
+        E: StdError + Send + Sync + 'static,
+    {
+        Self(MsgpackErrorImpl::Other(AnyhowError::new(e)))
+    }
+
+    fn decode(e: &rmp_serde::decode::Error, path: &Path) -> Self {
+        Self(MsgpackErrorImpl::Decode {
+            message: e.to_string(),
+            path: path.to_path_buf(),
+        })
+    }
+
+    fn encode(e: &rmp_serde::encode::Error, path: &Path) -> Self {
+        Self(MsgpackErrorImpl::Encode {
+            message: e.to_string(),
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+impl HasOtherError for MsgpackError {
+    fn is_other(&self) -> bool {
+        self.is_other()
+    }
+
+    fn downcast_other_any(&self) -> Option<&dyn Any> {
+        if let MsgpackErrorImpl::Other(ref inner) = self.0 {
+            Some(inner)
+        } else {
+            None
+        }
+    }
+
+    fn downcast_other<E>(self) -> StdResult<E, Self>
+    where
+        E: Display + Debug + Send + Sync + 'static,
+    {
+        match self.0 {
+            MsgpackErrorImpl::Other(inner) => inner
+                .downcast::<E>()
+                .map_err(|inner| Self(MsgpackErrorImpl::Other(inner))),
+            inner => Err(Self(inner)),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+enum MsgpackErrorImpl {
+    #[error("{message} in {path}")]
+    Decode { message: String, path: PathBuf },
+    #[error("{message} in {path}")]
+    Encode { message: String, path: PathBuf },
+    #[error(transparent)]
+    Other(AnyhowError),
+}
+
+impl PartialEq for MsgpackErrorImpl {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Decode { path: a, .. }, Self::Decode { path: b, .. })
+            | (Self::Encode { path: a, .. }, Self::Encode { path: b, .. }) => a == b,
+            (Self::Other(_), Self::Other(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+#[allow(unused)]
+pub fn read_msgpack_file<T>(path: impl AsRef<Path>) -> StdResult<T, MsgpackError>
+where
+    T: DeserializeOwned,
+{
+    let path = path.as_ref();
+    let bytes = read_bytes(path).map_err(MsgpackError::other)?;
+    let value = rmp_serde::from_slice::<T>(&bytes).map_err(|e| MsgpackError::decode(&e, path))?;
+    Ok(value)
+}
+
+/// Writes `value` to `path` in `MessagePack` format with struct fields
+/// encoded by name so that the file survives reordering of fields
+#[allow(unused)]
+pub fn write_msgpack_file<T>(
+    path: impl AsRef<Path>,
+    value: &T,
+    overwrite: bool,
+) -> StdResult<(), MsgpackError>
+where
+    T: Serialize,
+{
+    let path = path.as_ref();
+    let bytes = rmp_serde::to_vec_named(value).map_err(|e| MsgpackError::encode(&e, path))?;
+    safe_write_file(path, bytes, overwrite).map_err(MsgpackError::other)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_msgpack_file, write_msgpack_file, MsgpackErrorKind};
+    use anyhow::Result;
+    use serde::{Deserialize, Serialize};
+    use std::fs::write;
+    use tempdir::TempDir;
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Record {
+        message: String,
+        values: Vec<i32>,
+    }
+
+    #[test]
+    fn test_write_msgpack_file_round_trip() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.msgpack");
+        let value = Record {
+            message: String::from("hello-world"),
+            values: vec![1, 2, 3],
+        };
+
+        // Act
+        write_msgpack_file(&path, &value, false)?;
+        let result = read_msgpack_file::<Record>(&path)?;
+
+        // Assert
+        assert_eq!(value, result);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_msgpack_file_invalid_fails() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.msgpack");
+        write(&path, [0xc1])?;
+
+        // Act
+        let Err(e) = read_msgpack_file::<Record>(&path) else {
+            panic!("read_msgpack_file must fail")
+        };
+
+        // Assert
+        assert_eq!(MsgpackErrorKind::Decode, e.kind());
+        assert!(e.is_decode());
+        assert!(!e.is_other());
+        let message = format!("{e}");
+        assert!(message.contains(path.to_str().expect("must be valid string")));
+        Ok(())
+    }
+}
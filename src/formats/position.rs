@@ -0,0 +1,303 @@
+// Copyright (c) 2023 Richard Cook
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the
+// "Software"), to deal in the Software without restriction, including
+// without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to
+// permit persons to whom the Software is furnished to do so, subject to
+// the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE
+// LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION
+// WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+//
+use std::ops::Range;
+
+/// An error that may know where in its source file it occurred
+pub trait HasPosition {
+    /// Returns the 1-based line and column, counting characters, of the
+    /// error if known
+    fn line_col(&self) -> Option<(usize, usize)>;
+
+    /// Returns the range of bytes in the source file covered by the error
+    /// if known
+    fn byte_span(&self) -> Option<Range<usize>>;
+}
+
+/// Converts a byte offset into `source` into a 1-based line and column,
+/// where the column counts characters rather than bytes
+#[allow(unused)]
+#[must_use]
+pub fn offset_to_line_col(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source.as_bytes()[..offset.min(source.len())];
+    let mut line = 1;
+    let mut line_start = 0;
+    for (i, b) in before.iter().enumerate() {
+        if *b == b'\n' {
+            line += 1;
+            line_start = i + 1;
+        }
+    }
+    let column = String::from_utf8_lossy(&before[line_start..])
+        .chars()
+        .count()
+        + 1;
+    (line, column)
+}
+
+// Inverse of offset_to_line_col for positions within source
+pub(super) fn line_col_to_offset(source: &str, line: usize, column: usize) -> Option<usize> {
+    let (line_start, line_text) = find_line(source, line)?;
+    let offset = line_text
+        .char_indices()
+        .map(|(i, _)| i)
+        .chain(std::iter::once(line_text.len()))
+        .nth(column.checked_sub(1)?)?;
+    Some(line_start + offset)
+}
+
+// Converts a 1-based column counting bytes, as reported by serde_json,
+// into one counting characters on the given line of source
+pub(super) fn byte_column_to_char_column(source: &str, line: usize, column: usize) -> usize {
+    let Some((_, line_text)) = find_line(source, line) else {
+        return column;
+    };
+    line_text
+        .char_indices()
+        .take_while(|(i, _)| *i < column)
+        .count()
+        + column.saturating_sub(line_text.len())
+}
+
+// The lines of a source file around an error, retained in place of the
+// whole file so that errors stay small while still being able to render
+// a snippet and compute byte positions
+#[derive(Debug)]
+pub(super) struct SourceExcerpt {
+    first_line: usize,
+    offset: usize,
+    text: Box<str>,
+}
+
+impl SourceExcerpt {
+    const CONTEXT_LINES: usize = 2;
+
+    // Retains the 1-based line of source together with up to
+    // CONTEXT_LINES lines either side of it
+    pub(super) fn new(source: &str, line: usize) -> Option<Self> {
+        if line == 0 {
+            return None;
+        }
+        let first_line = line.saturating_sub(Self::CONTEXT_LINES).max(1);
+        let (offset, _) = find_line(source, first_line)?;
+        let end = source[offset..]
+            .match_indices('\n')
+            .nth(line + Self::CONTEXT_LINES - first_line)
+            .map_or(source.len(), |(i, _)| offset + i);
+        Some(Self {
+            first_line,
+            offset,
+            text: source[offset..end].into(),
+        })
+    }
+
+    // Returns the text of the 1-based line if it was retained
+    pub(super) fn line(&self, line: usize) -> Option<&str> {
+        self.text.lines().nth(line.checked_sub(self.first_line)?)
+    }
+
+    // Returns the byte offset into the source of the 1-based line and
+    // column, counting characters, if the line was retained
+    pub(super) fn offset(&self, line: usize, column: usize) -> Option<usize> {
+        let line = line.checked_sub(self.first_line)? + 1;
+        line_col_to_offset(&self.text, line, column).map(|o| self.offset + o)
+    }
+
+    // Returns the 1-based line and column, counting characters, of the
+    // byte offset into the source if it lies within the retained lines
+    pub(super) fn line_col(&self, offset: usize) -> Option<(usize, usize)> {
+        let o = offset.checked_sub(self.offset)?;
+        if o > self.text.len() {
+            return None;
+        }
+        let (line, column) = offset_to_line_col(&self.text, o);
+        Some((line + self.first_line - 1, column))
+    }
+
+    // Returns the range of bytes in the source covering the character at
+    // offset, or an empty range if offset lies outside the retained lines
+    pub(super) fn char_span(&self, offset: usize) -> Range<usize> {
+        match offset.checked_sub(self.offset) {
+            Some(o) if o <= self.text.len() => {
+                let span = char_span(&self.text, o);
+                self.offset + span.start..self.offset + span.end
+            }
+            _ => offset..offset,
+        }
+    }
+}
+
+// Returns the byte offset and text, without the newline, of the 1-based
+// line of source
+fn find_line(source: &str, line: usize) -> Option<(usize, &str)> {
+    let line_start = if line == 1 {
+        0
+    } else {
+        source
+            .match_indices('\n')
+            .nth(line.checked_sub(2)?)
+            .map(|(i, _)| i + 1)?
+    };
+    let line_text = &source[line_start..];
+    let line_text = &line_text[..line_text.find('\n').unwrap_or(line_text.len())];
+    Some((line_start, line_text))
+}
+
+// Returns the range covering the character at offset, or an empty range
+// at the end of source
+pub(super) fn char_span(source: &str, offset: usize) -> Range<usize> {
+    let offset = offset.min(source.len());
+    let len = source[offset..].chars().next().map_or(0, char::len_utf8);
+    offset..offset + len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        byte_column_to_char_column, char_span, line_col_to_offset, offset_to_line_col,
+        SourceExcerpt,
+    };
+    use rstest::rstest;
+
+    #[rstest]
+    #[case((1, 1), "", 0)]
+    #[case((1, 1), "aaa\nbbb", 0)]
+    #[case((1, 3), "aaa\nbbb", 2)]
+    #[case((2, 1), "aaa\nbbb", 4)]
+    #[case((2, 3), "aaa\nbbb", 6)]
+    #[case((2, 4), "aaa\nbbb", 100)]
+    fn test_offset_to_line_col(
+        #[case] expected: (usize, usize),
+        #[case] source: &str,
+        #[case] offset: usize,
+    ) {
+        // Act
+        let result = offset_to_line_col(source, offset);
+
+        // Assert
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn test_offset_to_line_col_multi_byte() {
+        // Arrange
+        let source = "aaa\nb\u{e9}\u{1f600}ccc";
+        let offset = source.find("ccc").expect("must be found");
+
+        // Act
+        let result = offset_to_line_col(source, offset);
+
+        // Assert
+        assert_eq!((2, 4), result);
+    }
+
+    #[rstest]
+    #[case(Some(0), "aaa\nbbb", 1, 1)]
+    #[case(Some(2), "aaa\nbbb", 1, 3)]
+    #[case(Some(4), "aaa\nbbb", 2, 1)]
+    #[case(Some(7), "aaa\nbbb", 2, 4)]
+    #[case(None, "aaa\nbbb", 2, 5)]
+    #[case(None, "aaa\nbbb", 3, 1)]
+    #[case(None, "aaa\nbbb", 0, 1)]
+    fn test_line_col_to_offset(
+        #[case] expected: Option<usize>,
+        #[case] source: &str,
+        #[case] line: usize,
+        #[case] column: usize,
+    ) {
+        // Act
+        let result = line_col_to_offset(source, line, column);
+
+        // Assert
+        assert_eq!(expected, result);
+    }
+
+    #[rstest]
+    #[case(3, "aaa\nbbb", 1, 3)]
+    #[case(4, "aaa\nb\u{e9}\u{1f600}ccc", 2, 8)]
+    #[case(5, "aaa\nbbb", 2, 5)]
+    #[case(4, "aaa\nbbb", 3, 4)]
+    fn test_byte_column_to_char_column(
+        #[case] expected: usize,
+        #[case] source: &str,
+        #[case] line: usize,
+        #[case] column: usize,
+    ) {
+        // Act
+        let result = byte_column_to_char_column(source, line, column);
+
+        // Assert
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn test_source_excerpt() {
+        // Arrange
+        let source = "aaa\nbbb\nccc\nd\u{e9}d\neee\nfff\nggg";
+
+        // Act
+        let excerpt = SourceExcerpt::new(source, 4).expect("must succeed");
+
+        // Assert
+        assert_eq!(None, excerpt.line(1));
+        assert_eq!(Some("bbb"), excerpt.line(2));
+        assert_eq!(Some("d\u{e9}d"), excerpt.line(4));
+        assert_eq!(Some("fff"), excerpt.line(6));
+        assert_eq!(None, excerpt.line(7));
+        let offset = source.find("d\u{e9}d").expect("must be found") + 3;
+        assert_eq!(Some(offset), excerpt.offset(4, 3));
+        assert_eq!(Some((4, 3)), excerpt.line_col(offset));
+        assert_eq!(None, excerpt.line_col(0));
+        assert_eq!(None, excerpt.offset(1, 1));
+        assert_eq!(offset - 2..offset, excerpt.char_span(offset - 2));
+        assert_eq!(0..0, excerpt.char_span(0));
+    }
+
+    #[rstest]
+    #[case(Some("aaa"), "aaa", 1)]
+    #[case(Some("bbb"), "aaa\nbbb\n", 2)]
+    #[case(None, "aaa", 2)]
+    #[case(None, "aaa", 0)]
+    fn test_source_excerpt_line(
+        #[case] expected: Option<&str>,
+        #[case] source: &str,
+        #[case] line: usize,
+    ) {
+        // Act
+        let excerpt = SourceExcerpt::new(source, line);
+
+        // Assert
+        assert_eq!(expected, excerpt.as_ref().and_then(|e| e.line(line)));
+    }
+
+    #[test]
+    fn test_char_span_multi_byte() {
+        // Arrange
+        let source = "a\u{e9}b";
+
+        // Act
+        let result = char_span(source, 1);
+
+        // Assert
+        assert_eq!(1..3, result);
+        assert_eq!(4..4, char_span(source, 4));
+    }
+}
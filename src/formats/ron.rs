@@ -0,0 +1,265 @@
+// Copyright (c) 2023 Richard Cook
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the
+// "Software"), to deal in the Software without restriction, including
+// without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to
+// permit persons to whom the Software is furnished to do so, subject to
+// the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE
+// LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION
+// WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+//
+use super::diagnostic::render_diagnostic;
+use super::position::SourceExcerpt;
+use crate::error::HasOtherError;
+use crate::fs::read_text_file_no_bom;
+use anyhow::Error as AnyhowError;
+use ron::error::{Position, SpannedError};
+use serde::de::DeserializeOwned;
+use std::any::Any;
+use std::error::Error as StdError;
+use std::fmt::{Debug, Display};
+use std::path::{Path, PathBuf};
+use std::result::Result as StdResult;
+use thiserror::Error;
+
+#[allow(unused)]
+#[derive(Debug, PartialEq)]
+#[non_exhaustive]
+pub enum RonErrorKind {
+    Syntax,
+    Other,
+}
+
+#[derive(Debug, Error, PartialEq)]
+#[error(transparent)]
+pub struct RonError(#[from] RonErrorImpl);
+
+impl RonError {
+    #[allow(unused)]
+    #[must_use]
+    pub const fn kind(&self) -> RonErrorKind {
+        match self.0 {
+            RonErrorImpl::Syntax { .. } => RonErrorKind::Syntax,
+            _ => RonErrorKind::Other,
+        }
+    }
+
+    /// Returns whether the error is of the given kind
+    #[allow(unused)]
+    #[allow(clippy::needless_pass_by_value)]
+    #[must_use]
+    pub fn is_kind(&self, kind: RonErrorKind) -> bool {
+        self.kind() == kind
+    }
+
+    #[allow(unused)]
+    #[must_use]
+    pub fn is_syntax(&self) -> bool {
+        self.kind() == RonErrorKind::Syntax
+    }
+
+    #[allow(unused)]
+    #[must_use]
+    pub fn is_other(&self) -> bool {
+        self.kind() == RonErrorKind::Other
+    }
+
+    fn other<E>(e: E) -> Self
+    where
+        E: StdError + Send + Sync + 'static,
+    {
+        Self(RonErrorImpl::Other(AnyhowError::new(e)))
+    }
+
+    fn convert(e: &SpannedError, path: &Path, contents: &str) -> Self {
+        Self(RonErrorImpl::Syntax {
+            message: e.code.to_string(),
+            position: e.position,
+            path: path.to_path_buf(),
+            excerpt: SourceExcerpt::new(contents, e.position.line),
+        })
+    }
+
+    /// Renders the error as a compiler-style diagnostic with a snippet
+    /// of the source at the error position
+    #[allow(unused)]
+    #[must_use]
+    pub fn display_pretty(&self, use_color: bool) -> String {
+        match self.0 {
+            RonErrorImpl::Syntax {
+                ref message,
+                ref position,
+                ref path,
+                ref excerpt,
+            } => render_diagnostic(
+                message,
+                Some(path),
+                excerpt.as_ref(),
+                Some((position.line, position.col)),
+                use_color,
+            ),
+            RonErrorImpl::Other(_) => {
+                render_diagnostic(&self.0.to_string(), None, None, None, use_color)
+            }
+        }
+    }
+}
+
+impl HasOtherError for RonError {
+    fn is_other(&self) -> bool {
+        self.is_other()
+    }
+
+    fn downcast_other_any(&self) -> Option<&dyn Any> {
+        if let RonErrorImpl::Other(ref inner) = self.0 {
+            Some(inner)
+        } else {
+            None
+        }
+    }
+
+    fn downcast_other<E>(self) -> StdResult<E, Self>
+    where
+        E: Display + Debug + Send + Sync + 'static,
+    {
+        match self.0 {
+            RonErrorImpl::Other(inner) => inner
+                .downcast::<E>()
+                .map_err(|inner| Self(RonErrorImpl::Other(inner))),
+            inner => Err(Self(inner)),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+enum RonErrorImpl {
+    #[error("{message} at line {} column {} in {}", .position.line, .position.col, .path.display())]
+    Syntax {
+        message: String,
+        position: Position,
+        path: PathBuf,
+        excerpt: Option<SourceExcerpt>,
+    },
+    #[error(transparent)]
+    Other(AnyhowError),
+}
+
+impl PartialEq for RonErrorImpl {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Syntax { path: a, .. }, Self::Syntax { path: b, .. }) => a == b,
+            (Self::Other(_), Self::Other(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+#[allow(unused)]
+pub fn read_ron_file<T>(path: impl AsRef<Path>) -> StdResult<T, RonError>
+where
+    T: DeserializeOwned,
+{
+    let path = path.as_ref();
+    let s = read_text_file_no_bom(path).map_err(RonError::other)?;
+    let value = ron::from_str::<T>(&s).map_err(|e| RonError::convert(&e, path, &s))?;
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_ron_file, RonErrorKind};
+    use anyhow::Result;
+    use serde::Deserialize;
+    use std::fs::write;
+    use tempdir::TempDir;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Config {
+        message: String,
+        count: i32,
+    }
+
+    #[test]
+    fn test_read_ron_file_succeeds() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.ron");
+        write(
+            &path,
+            "(\n    message: \"hello-world\",\n    count: 5,\n)\n",
+        )?;
+
+        // Act
+        let value = read_ron_file::<Config>(&path)?;
+
+        // Assert
+        assert_eq!(
+            Config {
+                message: String::from("hello-world"),
+                count: 5
+            },
+            value
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_ron_file_invalid_fails() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.ron");
+        write(
+            &path,
+            "(\n    message: \"hello-world\",\n    count: xxx,\n)\n",
+        )?;
+
+        // Act
+        let Err(e) = read_ron_file::<Config>(&path) else {
+            panic!("read_ron_file must fail")
+        };
+
+        // Assert
+        assert_eq!(RonErrorKind::Syntax, e.kind());
+        assert!(e.is_syntax());
+        assert!(!e.is_other());
+        let message = format!("{e}");
+        assert!(message.contains("at line 3 column 12"));
+        assert!(message.contains(path.to_str().expect("must be valid string")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_display_pretty() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.ron");
+        write(
+            &path,
+            "(\n    message: \"hello-world\",\n    count: xxx,\n)\n",
+        )?;
+        let Err(e) = read_ron_file::<Config>(&path) else {
+            panic!("read_ron_file must fail")
+        };
+
+        // Act
+        let output = e.display_pretty(false);
+
+        // Assert
+        assert!(output.starts_with("error: "));
+        assert!(output.contains(path.to_str().expect("must be valid string")));
+        assert!(output.lines().any(|line| line.trim_end().ends_with('^')));
+        assert!(!output.contains('\x1b'));
+        Ok(())
+    }
+}
@@ -19,97 +19,178 @@
 // OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION
 // WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
 //
+use super::diagnostic::render_diagnostic;
+use super::position::{offset_to_line_col, HasPosition, SourceExcerpt};
 use crate::error::HasOtherError;
-use crate::fs::read_text_file;
+use crate::fs::{read_text_file_no_bom, safe_write_file};
 use anyhow::Error as AnyhowError;
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveTime};
 use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::any::Any;
 use std::error::Error as StdError;
 use std::fmt::{Debug, Display};
+use std::ops::Range;
 use std::path::{Path, PathBuf};
 use std::result::Result as StdResult;
 use thiserror::Error;
 use toml::de::Error as TomlDeError;
-use toml_edit::{DocumentMut, TomlError as TomlEditError};
+use toml::value::{Datetime, Offset};
+use toml::{Table as TomlTable, Value as TomlValue};
+use toml_edit::{DocumentMut, InlineTable, Item, Table, TomlError as TomlEditError, Value};
 
 #[allow(unused)]
 #[derive(Debug, PartialEq)]
 #[non_exhaustive]
 pub enum TomlErrorKind {
+    Data,
+    DuplicateKey { key: String },
     Syntax,
     Other,
 }
 
-#[derive(Debug, Error)]
+#[derive(Debug, Error, PartialEq)]
 #[error(transparent)]
 pub struct TomlError(#[from] TomlErrorImpl);
 
 impl TomlError {
     #[allow(unused)]
     #[must_use]
-    pub const fn kind(&self) -> TomlErrorKind {
+    pub fn kind(&self) -> TomlErrorKind {
         match self.0 {
+            TomlErrorImpl::Data { .. } => TomlErrorKind::Data,
+            TomlErrorImpl::Syntax {
+                duplicate_key: Some(ref key),
+                ..
+            } => TomlErrorKind::DuplicateKey { key: key.clone() },
             TomlErrorImpl::Syntax { .. } => TomlErrorKind::Syntax,
             _ => TomlErrorKind::Other,
         }
     }
 
+    /// Returns whether the error is of the given kind
+    #[allow(unused)]
+    #[allow(clippy::needless_pass_by_value)]
+    #[must_use]
+    pub fn is_kind(&self, kind: TomlErrorKind) -> bool {
+        self.kind() == kind
+    }
+
+    #[allow(unused)]
+    #[must_use]
+    pub fn is_data(&self) -> bool {
+        self.kind() == TomlErrorKind::Data
+    }
+
+    #[allow(unused)]
+    #[must_use]
+    pub fn is_duplicate_key(&self) -> bool {
+        matches!(self.kind(), TomlErrorKind::DuplicateKey { .. })
+    }
+
     #[allow(unused)]
     #[must_use]
     pub fn is_syntax(&self) -> bool {
         self.kind() == TomlErrorKind::Syntax
     }
 
+    /// Returns the key, as written in the source, that was defined more
+    /// than once if this is a duplicate key error
+    #[allow(unused)]
+    #[must_use]
+    pub fn duplicate_key(&self) -> Option<&str> {
+        match self.0 {
+            TomlErrorImpl::Syntax {
+                duplicate_key: Some(ref key),
+                ..
+            } => Some(key),
+            _ => None,
+        }
+    }
+
     #[allow(unused)]
     #[must_use]
     pub fn is_other(&self) -> bool {
         self.kind() == TomlErrorKind::Other
     }
 
-    fn other<E>(e: E) -> Self
+    pub(super) fn other<E>(e: E) -> Self
     where
         E: StdError + Send + Sync + 'static,
     {
         Self(TomlErrorImpl::Other(AnyhowError::new(e)))
     }
 
-    fn convert(e: &TomlDeError, path: &Path) -> Self {
-        let message = if let Some(s) = e.span() {
-            format!(
-                "{} at span {}:{} in {}",
-                e.message(),
-                s.start,
-                s.end,
-                path.display()
-            )
-        } else {
-            format!("{} in {}", e.message(), path.display())
-        };
+    fn convert(e: &TomlDeError, path: &Path, contents: &str) -> Self {
+        Self::syntax(e.message(), e.span(), path, contents)
+    }
+
+    fn convert_edit(e: &TomlEditError, path: &Path, contents: &str) -> Self {
+        Self::syntax(e.message(), e.span(), path, contents)
+    }
 
+    fn syntax(reason: &str, span: Option<Range<usize>>, path: &Path, contents: &str) -> Self {
+        let excerpt = span.as_ref().and_then(|s| {
+            let (line, _) = offset_to_line_col(contents, s.start);
+            SourceExcerpt::new(contents, line).map(Box::new)
+        });
         Self(TomlErrorImpl::Syntax {
-            message,
+            reason: reason.to_string(),
             path: path.to_path_buf(),
-            span: e.span(),
+            span,
+            excerpt,
+            duplicate_key: parse_duplicate_key(reason),
         })
     }
 
-    fn convert_edit(e: &TomlEditError, path: &Path) -> Self {
-        let message = if let Some(s) = e.span() {
-            format!(
-                "{} at span {}:{} in {}",
-                e.message(),
-                s.start,
-                s.end,
-                path.display()
-            )
-        } else {
-            format!("{} in {}", e.message(), path.display())
-        };
+    fn excerpt(&self) -> Option<&SourceExcerpt> {
+        match self.0 {
+            TomlErrorImpl::Syntax { ref excerpt, .. } => excerpt.as_deref(),
+            _ => None,
+        }
+    }
 
-        Self(TomlErrorImpl::Syntax {
-            message,
-            path: path.to_path_buf(),
-            span: e.span(),
-        })
+    /// Renders the error as a compiler-style diagnostic with a snippet
+    /// of the source when a span is available
+    #[allow(unused)]
+    #[must_use]
+    pub fn display_pretty(&self, use_color: bool) -> String {
+        match self.0 {
+            TomlErrorImpl::Syntax {
+                ref reason,
+                ref path,
+                ..
+            } => render_diagnostic(
+                reason.trim_end(),
+                Some(path),
+                self.excerpt(),
+                self.line_col(),
+                use_color,
+            ),
+            TomlErrorImpl::Data { .. } | TomlErrorImpl::Other(_) => {
+                render_diagnostic(&self.0.to_string(), None, None, None, use_color)
+            }
+        }
+    }
+}
+
+impl HasPosition for TomlError {
+    fn line_col(&self) -> Option<(usize, usize)> {
+        match self.0 {
+            TomlErrorImpl::Syntax {
+                span: Some(ref span),
+                excerpt: Some(ref excerpt),
+                ..
+            } => excerpt.line_col(span.start),
+            _ => None,
+        }
+    }
+
+    fn byte_span(&self) -> Option<Range<usize>> {
+        match self.0 {
+            TomlErrorImpl::Syntax { ref span, .. } => span.clone(),
+            _ => None,
+        }
     }
 }
 
@@ -118,56 +199,442 @@ impl HasOtherError for TomlError {
         self.is_other()
     }
 
-    fn downcast_other_ref<E>(&self) -> Option<&E>
-    where
-        E: Display + Debug + Send + Sync + 'static,
-    {
+    fn downcast_other_any(&self) -> Option<&dyn Any> {
         if let TomlErrorImpl::Other(ref inner) = self.0 {
-            inner.downcast_ref::<E>()
+            Some(inner)
         } else {
             None
         }
     }
+
+    fn downcast_other<E>(self) -> StdResult<E, Self>
+    where
+        E: Display + Debug + Send + Sync + 'static,
+    {
+        match self.0 {
+            TomlErrorImpl::Other(inner) => inner
+                .downcast::<E>()
+                .map_err(|inner| Self(TomlErrorImpl::Other(inner))),
+            inner => Err(Self(inner)),
+        }
+    }
 }
 
 #[derive(Debug, Error)]
 enum TomlErrorImpl {
-    #[error("{message}")]
-    Syntax {
+    #[error("{}", data_message(.message, .path.as_deref()))]
+    Data {
         message: String,
+        path: Option<PathBuf>,
+    },
+    #[error("{}", syntax_message(.reason, .path, .span.as_ref()))]
+    Syntax {
+        reason: String,
         path: PathBuf,
-        span: Option<std::ops::Range<usize>>,
+        span: Option<Range<usize>>,
+        excerpt: Option<Box<SourceExcerpt>>,
+        duplicate_key: Option<String>,
     },
     #[error(transparent)]
     Other(AnyhowError),
 }
 
+impl PartialEq for TomlErrorImpl {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (
+                Self::Data {
+                    message: a,
+                    path: path_a,
+                },
+                Self::Data {
+                    message: b,
+                    path: path_b,
+                },
+            ) => a == b && path_a == path_b,
+            (Self::Syntax { path: a, .. }, Self::Syntax { path: b, .. }) => a == b,
+            (Self::Other(_), Self::Other(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+// The toml crates do not expose the kind of a parse error so recognize
+// duplicate keys from messages such as "duplicate key `name` in table
+// `package`"
+fn parse_duplicate_key(reason: &str) -> Option<String> {
+    const PREFIX: &str = "duplicate key `";
+    let start = reason.find(PREFIX)? + PREFIX.len();
+    let len = reason[start..].find('`')?;
+    Some(reason[start..start + len].to_string())
+}
+
+fn data_message(message: &str, path: Option<&Path>) -> String {
+    match path {
+        Some(p) => format!("{} in {}", message, p.display()),
+        None => message.to_string(),
+    }
+}
+
+fn syntax_message(reason: &str, path: &Path, span: Option<&Range<usize>>) -> String {
+    if let Some(s) = span {
+        format!(
+            "{} at span {}:{} in {}",
+            reason,
+            s.start,
+            s.end,
+            path.display()
+        )
+    } else {
+        format!("{} in {}", reason, path.display())
+    }
+}
+
 #[allow(unused)]
-pub fn read_toml_file<T>(path: &Path) -> StdResult<T, TomlError>
+pub fn read_toml_file<T>(path: impl AsRef<Path>) -> StdResult<T, TomlError>
 where
     T: DeserializeOwned,
 {
-    let s = read_text_file(path).map_err(TomlError::other)?;
-    let value = toml::from_str::<T>(&s).map_err(|e| TomlError::convert(&e, path))?;
+    let path = path.as_ref();
+    let s = read_text_file_no_bom(path).map_err(TomlError::other)?;
+    let value = toml::from_str::<T>(&s).map_err(|e| TomlError::convert(&e, path, &s))?;
     Ok(value)
 }
 
 #[allow(unused)]
-pub fn read_toml_file_edit(path: &Path) -> StdResult<DocumentMut, TomlError> {
-    let s = read_text_file(path).map_err(TomlError::other)?;
+pub fn read_toml_file_edit(path: impl AsRef<Path>) -> StdResult<DocumentMut, TomlError> {
+    let path = path.as_ref();
+    let s = read_text_file_no_bom(path).map_err(TomlError::other)?;
     let doc = s
         .parse::<DocumentMut>()
-        .map_err(|e| TomlError::convert_edit(&e, path))?;
+        .map_err(|e| TomlError::convert_edit(&e, path, &s))?;
     Ok(doc)
 }
 
+/// Reads the TOML file at `path` once and returns both the deserialized
+/// value and the editable document
+#[allow(unused)]
+pub fn read_toml_file_both<T>(path: impl AsRef<Path>) -> StdResult<(T, DocumentMut), TomlError>
+where
+    T: DeserializeOwned,
+{
+    let path = path.as_ref();
+    let s = read_text_file_no_bom(path).map_err(TomlError::other)?;
+    let doc = s
+        .parse::<DocumentMut>()
+        .map_err(|e| TomlError::convert_edit(&e, path, &s))?;
+    let value = toml::from_str::<T>(&s).map_err(|e| TomlError::convert(&e, path, &s))?;
+    Ok((value, doc))
+}
+
+/// Reads each of the TOML files in `paths` that exists and deep-merges
+/// them in order, with values from later files replacing those from
+/// earlier files, before deserializing the result
+#[allow(unused)]
+pub fn read_layered_toml<T>(paths: &[impl AsRef<Path>]) -> StdResult<T, TomlError>
+where
+    T: DeserializeOwned,
+{
+    let mut merged = TomlValue::Table(TomlTable::new());
+    for path in paths {
+        let path = path.as_ref();
+        if path.exists() {
+            merge_toml(&mut merged, read_toml_file::<TomlValue>(path)?);
+        }
+    }
+
+    // The merged value does not come from any one file so the error
+    // cannot name a path
+    T::deserialize(merged).map_err(|e| {
+        TomlError(TomlErrorImpl::Data {
+            message: e.message().trim_end().to_string(),
+            path: None,
+        })
+    })
+}
+
+/// Reads the array of tables at dotted `key` in the TOML file at `path`
+/// and deserializes each element, reporting the index of any element that
+/// fails in a data error
+#[allow(unused)]
+pub fn read_toml_array_of_tables<T>(
+    path: impl AsRef<Path>,
+    key: &str,
+) -> StdResult<Vec<T>, TomlError>
+where
+    T: DeserializeOwned,
+{
+    let path = path.as_ref();
+    let mut value = read_toml_file::<TomlValue>(path)?;
+    for segment in key.split('.') {
+        value = match value {
+            TomlValue::Table(mut t) => t.remove(segment),
+            _ => None,
+        }
+        .ok_or_else(|| {
+            TomlError(TomlErrorImpl::Data {
+                message: format!("Missing key {key}"),
+                path: Some(path.to_path_buf()),
+            })
+        })?;
+    }
+
+    let TomlValue::Array(items) = value else {
+        return Err(TomlError(TomlErrorImpl::Data {
+            message: format!(
+                "Expected array of tables at {key} but found {}",
+                value.type_str()
+            ),
+            path: Some(path.to_path_buf()),
+        }));
+    };
+
+    items
+        .into_iter()
+        .enumerate()
+        .map(|(i, item)| {
+            T::deserialize(item).map_err(|e| {
+                TomlError(TomlErrorImpl::Data {
+                    message: format!("Element {i} of {key}: {}", e.message().trim_end()),
+                    path: Some(path.to_path_buf()),
+                })
+            })
+        })
+        .collect()
+}
+
+/// Deep-merges `overlay` into `base`: tables are merged key by key and
+/// any other overlay value, including an array, replaces the base value
+#[allow(unused)]
+pub fn merge_toml(base: &mut TomlValue, overlay: TomlValue) {
+    match (base, overlay) {
+        (TomlValue::Table(base), TomlValue::Table(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(b) => merge_toml(b, value),
+                    None => _ = base.insert(key, value),
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Converts a TOML datetime into a `chrono` datetime
+///
+/// Local datetimes and dates without an offset are treated as UTC and
+/// dates without a time as midnight. Returns `None` for a time without a
+/// date or for values out of range
+#[allow(unused)]
+#[must_use]
+pub fn toml_datetime_to_chrono(dt: &Datetime) -> Option<DateTime<FixedOffset>> {
+    let date = dt.date?;
+    let date = NaiveDate::from_ymd_opt(
+        i32::from(date.year),
+        u32::from(date.month),
+        u32::from(date.day),
+    )?;
+    let time = match dt.time {
+        Some(time) => NaiveTime::from_hms_nano_opt(
+            u32::from(time.hour),
+            u32::from(time.minute),
+            u32::from(time.second),
+            time.nanosecond,
+        )?,
+        None => NaiveTime::MIN,
+    };
+    let offset = match dt.offset {
+        Some(Offset::Custom { minutes }) => FixedOffset::east_opt(i32::from(minutes) * 60)?,
+        Some(Offset::Z) | None => FixedOffset::east_opt(0)?,
+    };
+    date.and_time(time).and_local_timezone(offset).single()
+}
+
+/// Returns the string at dotted `path` in `doc`, failing with a data
+/// error if a key along the path is missing or the value is not a string
+#[allow(unused)]
+pub fn doc_get_str<'a>(doc: &'a DocumentMut, path: &str) -> StdResult<&'a str, TomlError> {
+    let mut item = doc.as_item();
+    for key in path.split('.') {
+        item = item.get(key).ok_or_else(|| {
+            TomlError(TomlErrorImpl::Data {
+                message: format!("Missing key {path}"),
+                path: None,
+            })
+        })?;
+    }
+
+    item.as_str().ok_or_else(|| {
+        TomlError(TomlErrorImpl::Data {
+            message: format!("Expected string at {path} but found {}", item.type_name()),
+            path: None,
+        })
+    })
+}
+
+/// Removes the key at dotted `path` from `doc`, leaving the formatting of
+/// other keys intact, and returns whether a key was removed
+#[allow(unused)]
+pub fn doc_remove_path(doc: &mut DocumentMut, path: &str) -> bool {
+    let (parent, key) = match path.rsplit_once('.') {
+        Some((parent, key)) => (Some(parent), key),
+        None => (None, path),
+    };
+
+    let mut item = doc.as_item_mut();
+    if let Some(parent) = parent {
+        for segment in parent.split('.') {
+            match item.get_mut(segment) {
+                Some(i) => item = i,
+                None => return false,
+            }
+        }
+    }
+
+    item.as_table_like_mut()
+        .and_then(|t| t.remove(key))
+        .is_some()
+}
+
+/// Sets the key at `dotted_key` in the TOML file at `path` to `value`,
+/// creating any missing intermediate tables, and writes the file back
+/// with the formatting and comments of other keys intact
+#[allow(unused)]
+pub fn toml_set_in_file(
+    path: impl AsRef<Path>,
+    dotted_key: &str,
+    mut value: Value,
+) -> StdResult<(), TomlError> {
+    let path = path.as_ref();
+    let mut doc = read_toml_file_edit(path)?;
+    let (parent, key) = match dotted_key.rsplit_once('.') {
+        Some((parent, key)) => (Some(parent), key),
+        None => (None, dotted_key),
+    };
+
+    let mut item = doc.as_item_mut();
+    let mut prefix = String::new();
+    for segment in parent.into_iter().flat_map(|p| p.split('.')) {
+        let inline = item.is_inline_table();
+        let type_name = item.type_name();
+        let Some(table) = item.as_table_like_mut() else {
+            return Err(TomlError(TomlErrorImpl::Data {
+                message: format!("Expected table at {prefix} but found {type_name}"),
+                path: Some(path.to_path_buf()),
+            }));
+        };
+        let new_table = if inline {
+            Item::Value(Value::InlineTable(InlineTable::new()))
+        } else {
+            let mut t = Table::new();
+            t.set_implicit(true);
+            Item::Table(t)
+        };
+        item = table.entry(segment).or_insert(new_table);
+        if !prefix.is_empty() {
+            prefix.push('.');
+        }
+        prefix.push_str(segment);
+    }
+
+    let type_name = item.type_name();
+    let Some(table) = item.as_table_like_mut() else {
+        return Err(TomlError(TomlErrorImpl::Data {
+            message: format!("Expected table at {prefix} but found {type_name}"),
+            path: Some(path.to_path_buf()),
+        }));
+    };
+    if let Some(existing) = table.get_mut(key) {
+        // Assign in place so the key and its decoration are kept
+        if let Some(existing_value) = existing.as_value() {
+            *value.decor_mut() = existing_value.decor().clone();
+        }
+        *existing = Item::Value(value);
+    } else {
+        table.insert(key, Item::Value(value));
+    }
+
+    safe_write_file(path, doc.to_string(), true).map_err(TomlError::other)?;
+    Ok(())
+}
+
+/// Writes `value` to `path` as TOML with the keys of every table sorted
+/// lexicographically
+#[allow(unused)]
+pub fn write_toml_file_sorted<T>(
+    path: impl AsRef<Path>,
+    value: &T,
+    overwrite: bool,
+) -> StdResult<(), TomlError>
+where
+    T: Serialize,
+{
+    let path = path.as_ref();
+    let s = toml::to_string(value).map_err(TomlError::other)?;
+    let mut doc = s
+        .parse::<DocumentMut>()
+        .map_err(|e| TomlError::convert_edit(&e, path, &s))?;
+    let mut position = 0;
+    sort_table(doc.as_table_mut(), &mut position);
+    safe_write_file(path, doc.to_string(), overwrite).map_err(TomlError::other)?;
+    Ok(())
+}
+
+// Tables are rendered in order of position so this assigns positions to
+// subtables in key order as well as sorting each table's values: decor is
+// cleared so that blank lines between tables are regenerated
+fn sort_table(table: &mut Table, position: &mut usize) {
+    table.decor_mut().clear();
+    table.set_position(*position);
+    *position += 1;
+    table.sort_values();
+
+    let mut keys = table.iter().map(|(k, _)| k.to_string()).collect::<Vec<_>>();
+    keys.sort();
+    for key in keys {
+        match table.get_mut(&key) {
+            Some(Item::Table(t)) => sort_table(t, position),
+            Some(Item::ArrayOfTables(a)) => {
+                for t in a.iter_mut() {
+                    sort_table(t, position);
+                }
+            }
+            Some(Item::Value(v)) => sort_value(v),
+            _ => {}
+        }
+    }
+}
+
+fn sort_value(value: &mut Value) {
+    match value {
+        Value::InlineTable(t) => sort_inline_table(t),
+        Value::Array(a) => a.iter_mut().for_each(sort_value),
+        _ => {}
+    }
+}
+
+fn sort_inline_table(table: &mut InlineTable) {
+    table.sort_values();
+    table.iter_mut().for_each(|(_, v)| sort_value(v));
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{read_toml_file, read_toml_file_edit, TomlErrorKind};
+    use super::{
+        doc_get_str, doc_remove_path, merge_toml, read_layered_toml, read_toml_array_of_tables,
+        read_toml_file, read_toml_file_both, read_toml_file_edit, toml_datetime_to_chrono,
+        toml_set_in_file, write_toml_file_sorted, TomlErrorKind,
+    };
     use anyhow::Result;
-    use std::fs::write;
+    use chrono::DateTime;
+    use rstest::rstest;
+    use serde::Deserialize;
+    use std::collections::HashMap;
+    use std::fs::{read_to_string, write};
     use tempdir::TempDir;
+    use toml::value::Datetime;
     use toml::{toml, Value};
+    use toml_edit::DocumentMut;
 
     #[test]
     fn test_read_toml_file_succeeds() -> Result<()> {
@@ -184,6 +651,25 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_read_toml_file_bom_succeeds() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let bom_path = temp_dir.path().join("bom.toml");
+        let no_bom_path = temp_dir.path().join("no-bom.toml");
+        write(&bom_path, b"\xef\xbb\xbfmessage = \"hello-world\"")?;
+        write(&no_bom_path, "message = \"hello-world\"")?;
+
+        // Act
+        let bom_value = read_toml_file::<toml::Table>(&bom_path)?;
+        let no_bom_value = read_toml_file::<toml::Table>(&no_bom_path)?;
+
+        // Assert
+        assert_eq!(no_bom_value, bom_value);
+        assert_eq!(toml!(message = "hello-world"), bom_value);
+        Ok(())
+    }
+
     #[test]
     fn test_read_toml_file_invalid_fails() -> Result<()> {
         // Arrange
@@ -206,6 +692,58 @@ mod tests {
         Ok(())
     }
 
+    #[rstest]
+    #[case("name", "name = \"aaa\"\nname = \"bbb\"\n")]
+    #[case("version", "[package]\nversion = \"1.0\"\nversion = \"2.0\"\n")]
+    #[case("package", "[package]\nname = \"aaa\"\n[package]\nname = \"bbb\"\n")]
+    fn test_read_toml_file_duplicate_key_fails(
+        #[case] expected_key: &str,
+        #[case] content: &str,
+    ) -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.toml");
+        write(&path, content)?;
+
+        // Act
+        let Err(e) = read_toml_file::<Value>(&path) else {
+            panic!("read_toml_file must fail")
+        };
+
+        // Assert
+        assert_eq!(
+            TomlErrorKind::DuplicateKey {
+                key: String::from(expected_key)
+            },
+            e.kind()
+        );
+        assert!(e.is_duplicate_key());
+        assert!(!e.is_syntax());
+        assert_eq!(Some(expected_key), e.duplicate_key());
+        let message = format!("{e}");
+        assert!(message.contains(&format!("`{expected_key}`")));
+        assert!(message.contains(path.to_str().expect("must be valid string")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_toml_file_invalid_not_duplicate_key() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.toml");
+        write(&path, "name = \n")?;
+
+        // Act
+        let Err(e) = read_toml_file::<Value>(&path) else {
+            panic!("read_toml_file must fail")
+        };
+
+        // Assert
+        assert_eq!(TomlErrorKind::Syntax, e.kind());
+        assert_eq!(None, e.duplicate_key());
+        Ok(())
+    }
+
     #[test]
     fn test_read_toml_file_edit_succeeds() -> Result<()> {
         // Arrange
@@ -267,4 +805,446 @@ toml_edit = "0.19.8"
         assert!(message.contains(path.to_str().expect("must be valid string")));
         Ok(())
     }
+
+    #[test]
+    fn test_display_pretty() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.toml");
+        write(&path, "aaa = 1\nmessage = xxx\n")?;
+        let Err(e) = read_toml_file::<Value>(&path) else {
+            panic!("read_toml_file must fail")
+        };
+
+        // Act
+        let output = e.display_pretty(false);
+
+        // Assert
+        assert!(output.starts_with("error: "));
+        assert!(output.contains(path.to_str().expect("must be valid string")));
+        assert!(output.lines().any(|line| line.trim_end().ends_with('^')));
+        assert!(!output.contains('\x1b'));
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_toml_file_both_succeeds() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.toml");
+        write(&path, "# comment\nmessage = \"hello-world\"\n")?;
+
+        // Act
+        let (value, doc) = read_toml_file_both::<toml::Table>(&path)?;
+
+        // Assert
+        assert_eq!(toml!(message = "hello-world"), value);
+        assert_eq!(Some("hello-world"), doc["message"].as_str());
+        assert_eq!("# comment\nmessage = \"hello-world\"\n", doc.to_string());
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_toml_file_sorted_succeeds() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.toml");
+        let mut value = HashMap::new();
+        for key in ["ddd", "bbb", "eee", "aaa", "ccc"] {
+            let mut table = HashMap::new();
+            for inner_key in ["zzz", "xxx", "yyy"] {
+                table.insert(inner_key, 1);
+            }
+            value.insert(key, table);
+        }
+
+        // Act
+        write_toml_file_sorted(&path, &value, false)?;
+
+        // Assert
+        let expected = ["aaa", "bbb", "ccc", "ddd", "eee"]
+            .iter()
+            .map(|k| format!("[{k}]\nxxx = 1\nyyy = 1\nzzz = 1\n"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(expected, read_to_string(&path)?);
+        Ok(())
+    }
+
+    #[rstest]
+    #[case("1979-05-27T07:32:00+00:00", "1979-05-27T07:32:00Z")]
+    #[case("2023-04-05T06:07:08.5+01:30", "2023-04-05T06:07:08.5+01:30")]
+    #[case("2023-04-05T06:07:08+00:00", "2023-04-05T06:07:08")]
+    #[case("2023-04-05T00:00:00+00:00", "2023-04-05")]
+    fn test_toml_datetime_to_chrono(#[case] expected: &str, #[case] input: &str) {
+        // Arrange
+        let dt = input.parse::<Datetime>().expect("must be valid datetime");
+
+        // Act
+        let result = toml_datetime_to_chrono(&dt);
+
+        // Assert
+        assert_eq!(
+            Some(DateTime::parse_from_rfc3339(expected).expect("must be valid datetime")),
+            result
+        );
+    }
+
+    #[test]
+    fn test_toml_datetime_to_chrono_time_only() {
+        // Arrange
+        let dt = "06:07:08".parse::<Datetime>().expect("must be valid time");
+
+        // Act
+        let result = toml_datetime_to_chrono(&dt);
+
+        // Assert
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn test_doc_get_str_succeeds() -> Result<()> {
+        // Arrange
+        let doc = "[package]\nversion = \"1.2.3\"\n".parse::<DocumentMut>()?;
+
+        // Act
+        let result = doc_get_str(&doc, "package.version")?;
+
+        // Assert
+        assert_eq!("1.2.3", result);
+        Ok(())
+    }
+
+    #[test]
+    fn test_doc_get_str_missing_fails() -> Result<()> {
+        // Arrange
+        let doc = "[package]\nname = \"joatmon\"\n".parse::<DocumentMut>()?;
+
+        // Act
+        let Err(e) = doc_get_str(&doc, "package.version") else {
+            panic!("doc_get_str must fail")
+        };
+
+        // Assert
+        assert_eq!(TomlErrorKind::Data, e.kind());
+        assert_eq!("Missing key package.version", e.to_string());
+        Ok(())
+    }
+
+    #[test]
+    fn test_doc_get_str_wrong_type_fails() -> Result<()> {
+        // Arrange
+        let doc = "[package]\nversion = 123\n".parse::<DocumentMut>()?;
+
+        // Act
+        let Err(e) = doc_get_str(&doc, "package.version") else {
+            panic!("doc_get_str must fail")
+        };
+
+        // Assert
+        assert_eq!(TomlErrorKind::Data, e.kind());
+        assert_eq!(
+            "Expected string at package.version but found integer",
+            e.to_string()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_doc_remove_path_preserves_formatting() -> Result<()> {
+        // Arrange
+        let s = "[package]\nname = \"joatmon\" # name\n\n[dependencies]\naaa   = \"1.0\"\noldcrate = { version = \"0.1\" }\nbbb = \"2.0\"  # keep\n";
+        let mut doc = s.parse::<DocumentMut>()?;
+
+        // Act
+        let removed = doc_remove_path(&mut doc, "dependencies.oldcrate");
+
+        // Assert
+        assert!(removed);
+        assert_eq!(
+            s.replace("oldcrate = { version = \"0.1\" }\n", ""),
+            doc.to_string()
+        );
+        Ok(())
+    }
+
+    #[rstest]
+    #[case("dependencies.missing")]
+    #[case("missing.oldcrate")]
+    #[case("dependencies.oldcrate.version.extra")]
+    fn test_doc_remove_path_missing(#[case] path: &str) -> Result<()> {
+        // Arrange
+        let s = "[dependencies]\noldcrate = { version = \"0.1\" }\n";
+        let mut doc = s.parse::<DocumentMut>()?;
+
+        // Act
+        let removed = doc_remove_path(&mut doc, path);
+
+        // Assert
+        assert!(!removed);
+        assert_eq!(s, doc.to_string());
+        Ok(())
+    }
+
+    #[test]
+    fn test_toml_set_in_file_bumps_version() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("Cargo.toml");
+        let s = "# Manifest\n[package]\nname = \"joatmon\" # name\n\n[dependencies]\naaa   = \"1.0\"  # keep\nbbb = { version = \"0.1\", features = [\"x\"] }\n";
+        write(&path, s)?;
+
+        // Act
+        toml_set_in_file(&path, "dependencies.aaa", toml_edit::Value::from("1.1"))?;
+        toml_set_in_file(
+            &path,
+            "dependencies.bbb.version",
+            toml_edit::Value::from("0.2"),
+        )?;
+
+        // Assert
+        assert_eq!(
+            s.replace("\"1.0\"", "\"1.1\"")
+                .replace("\"0.1\"", "\"0.2\""),
+            read_to_string(&path)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_toml_set_in_file_creates_tables() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.toml");
+        write(&path, "[package]\nname = \"joatmon\"\n")?;
+
+        // Act
+        toml_set_in_file(&path, "profile.release.lto", toml_edit::Value::from(true))?;
+
+        // Assert
+        let value = read_toml_file::<Value>(&path)?;
+        assert_eq!(
+            Value::Table(toml! {
+                [package]
+                name = "joatmon"
+
+                [profile.release]
+                lto = true
+            }),
+            value
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_toml_set_in_file_not_table_fails() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.toml");
+        let s = "[package]\nname = \"joatmon\"\n";
+        write(&path, s)?;
+
+        // Act
+        let Err(e) = toml_set_in_file(&path, "package.name.first", toml_edit::Value::from("x"))
+        else {
+            panic!("toml_set_in_file must fail")
+        };
+
+        // Assert
+        assert_eq!(TomlErrorKind::Data, e.kind());
+        assert_eq!(
+            format!(
+                "Expected table at package.name but found string in {}",
+                path.display()
+            ),
+            e.to_string()
+        );
+        assert_eq!(s, read_to_string(&path)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_layered_toml_succeeds() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let defaults_path = temp_dir.path().join("defaults.toml");
+        let local_path = temp_dir.path().join("local.toml");
+        let missing_path = temp_dir.path().join("missing.toml");
+        write(
+            &defaults_path,
+            "name = \"joatmon\"\n[server]\nhost = \"localhost\"\nport = 80\n",
+        )?;
+        write(&local_path, "[server]\nport = 8080\n")?;
+
+        // Act
+        let value = read_layered_toml::<Value>(&[&defaults_path, &local_path, &missing_path])?;
+
+        // Assert
+        assert_eq!(
+            Value::Table(toml! {
+                name = "joatmon"
+
+                [server]
+                host = "localhost"
+                port = 8080
+            }),
+            value
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_layered_toml_as_ref_path() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.toml");
+        write(&path, "message = \"hello-world\"\n")?;
+        let s = path.to_str().expect("must be valid string");
+
+        // Act
+        let from_str = read_layered_toml::<Value>(&[s])?;
+        let from_string = read_layered_toml::<Value>(&[String::from(s)])?;
+        let from_path_buf = read_layered_toml::<Value>(&[path])?;
+
+        // Assert
+        let expected = Value::Table(toml! {
+            message = "hello-world"
+        });
+        assert_eq!(expected, from_str);
+        assert_eq!(expected, from_string);
+        assert_eq!(expected, from_path_buf);
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_toml_nested_tables() {
+        // Arrange
+        let mut base = Value::Table(toml! {
+            [server]
+            host = "localhost"
+
+            [server.tls]
+            enabled = false
+            cert = "a.pem"
+        });
+        let overlay = Value::Table(toml! {
+            [server]
+            port = 8080
+
+            [server.tls]
+            enabled = true
+        });
+
+        // Act
+        merge_toml(&mut base, overlay);
+
+        // Assert
+        assert_eq!(
+            Value::Table(toml! {
+                [server]
+                host = "localhost"
+                port = 8080
+
+                [server.tls]
+                enabled = true
+                cert = "a.pem"
+            }),
+            base
+        );
+    }
+
+    #[test]
+    fn test_merge_toml_scalar_and_array_override() {
+        // Arrange
+        let mut base = Value::Table(toml! {
+            name = "aaa"
+            tags = ["x", "y"]
+            server = { port = 80 }
+        });
+        let overlay = Value::Table(toml! {
+            name = "bbb"
+            tags = ["z"]
+            server = 8080
+        });
+
+        // Act
+        merge_toml(&mut base, overlay);
+
+        // Assert
+        assert_eq!(
+            Value::Table(toml! {
+                name = "bbb"
+                tags = ["z"]
+                server = 8080
+            }),
+            base
+        );
+    }
+
+    #[test]
+    fn test_toml_error_has_position() -> Result<()> {
+        use crate::formats::HasPosition;
+
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.toml");
+        write(&path, "message = \"hello\"\nbad = \n")?;
+
+        // Act
+        let Err(e) = read_toml_file::<Value>(&path) else {
+            panic!("read_toml_file must fail")
+        };
+
+        // Assert
+        let (line, _) = e.line_col().expect("must have line and column");
+        assert_eq!(2, line);
+        let span = e.byte_span().expect("must have span");
+        assert!(span.start >= "message = \"hello\"\n".len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_toml_array_of_tables_reports_index() -> Result<()> {
+        #[derive(Debug, Deserialize)]
+        struct Bin {
+            #[allow(unused)]
+            name: String,
+        }
+
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.toml");
+        write(&path, "[[bin]]\nname = \"aaa\"\n\n[[bin]]\nname = 123\n")?;
+
+        // Act
+        let Err(e) = read_toml_array_of_tables::<Bin>(&path, "bin") else {
+            panic!("read_toml_array_of_tables must fail")
+        };
+
+        // Assert
+        assert_eq!(TomlErrorKind::Data, e.kind());
+        let message = e.to_string();
+        assert!(message.starts_with("Element 1 of bin: "));
+        assert!(message.contains(path.to_str().expect("must be valid string")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_toml_array_of_tables_succeeds() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.toml");
+        write(
+            &path,
+            "[[bin]]\nname = \"aaa\"\n\n[[bin]]\nname = \"bbb\"\n",
+        )?;
+
+        // Act
+        let result = read_toml_array_of_tables::<HashMap<String, String>>(&path, "bin")?;
+
+        // Assert
+        assert_eq!(2, result.len());
+        assert_eq!("bbb", result[1]["name"]);
+        Ok(())
+    }
 }
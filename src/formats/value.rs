@@ -0,0 +1,80 @@
+// Copyright (c) 2023 Richard Cook
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the
+// "Software"), to deal in the Software without restriction, including
+// without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to
+// permit persons to whom the Software is furnished to do so, subject to
+// the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE
+// LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION
+// WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+//
+use serde_json::Value;
+
+/// Looks up a nested value by a dotted path such as `server.port`, with
+/// numeric segments indexing into arrays
+#[allow(unused)]
+#[must_use]
+pub fn value_get_path<'a>(value: &'a Value, dotted: &str) -> Option<&'a Value> {
+    if dotted.is_empty() {
+        return Some(value);
+    }
+
+    dotted.split('.').try_fold(value, |v, segment| match v {
+        Value::Object(m) => m.get(segment),
+        Value::Array(a) => segment.parse::<usize>().ok().and_then(|i| a.get(i)),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::value_get_path;
+    use serde_json::json;
+
+    #[test]
+    fn test_value_get_path_object() {
+        // Arrange
+        let value = json!({"server": {"host": "localhost", "port": 8080}});
+
+        // Act
+        let result = value_get_path(&value, "server.port");
+
+        // Assert
+        assert_eq!(Some(&json!(8080)), result);
+    }
+
+    #[test]
+    fn test_value_get_path_array() {
+        // Arrange
+        let value = json!({"servers": [{"port": 8080}, {"port": 9090}]});
+
+        // Act
+        let result = value_get_path(&value, "servers.1.port");
+
+        // Assert
+        assert_eq!(Some(&json!(9090)), result);
+    }
+
+    #[test]
+    fn test_value_get_path_missing() {
+        // Arrange
+        let value = json!({"servers": [{"port": 8080}]});
+
+        // Act & Assert
+        assert!(value_get_path(&value, "servers.1.port").is_none());
+        assert!(value_get_path(&value, "servers.x").is_none());
+        assert!(value_get_path(&value, "servers.0.port.aaa").is_none());
+        assert!(value_get_path(&value, "clients").is_none());
+    }
+}
@@ -19,13 +19,21 @@
 // OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION
 // WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
 //
+use super::diagnostic::render_diagnostic;
+use super::position::{HasPosition, SourceExcerpt};
 use crate::error::HasOtherError;
-use crate::fs::read_text_file;
+use crate::fs::{read_text_file_no_bom, safe_write_file, FileReadError};
 use anyhow::Error as AnyhowError;
 use serde::de::DeserializeOwned;
-use serde_yaml::{Error as SerdeYamlError, Location};
+use serde::ser::Error as SerError;
+use serde::Serialize;
+use serde_yaml::{Error as SerdeYamlError, Location, Value};
+use std::any::Any;
+use std::collections::HashMap;
 use std::error::Error as StdError;
 use std::fmt::{Debug, Display};
+use std::fs::read_dir;
+use std::ops::Range;
 use std::path::{Path, PathBuf};
 use std::result::Result as StdResult;
 use thiserror::Error;
@@ -38,7 +46,7 @@ pub enum YamlErrorKind {
     Other,
 }
 
-#[derive(Debug, Error)]
+#[derive(Debug, Error, PartialEq)]
 #[error(transparent)]
 pub struct YamlError(#[from] YamlErrorImpl);
 
@@ -52,6 +60,14 @@ impl YamlError {
         }
     }
 
+    /// Returns whether the error is of the given kind
+    #[allow(unused)]
+    #[allow(clippy::needless_pass_by_value)]
+    #[must_use]
+    pub fn is_kind(&self, kind: YamlErrorKind) -> bool {
+        self.kind() == kind
+    }
+
     #[allow(unused)]
     #[must_use]
     pub fn is_syntax(&self) -> bool {
@@ -64,20 +80,86 @@ impl YamlError {
         self.kind() == YamlErrorKind::Other
     }
 
-    fn other<E>(e: E) -> Self
+    pub(super) fn other<E>(e: E) -> Self
     where
         E: StdError + Send + Sync + 'static,
     {
         Self(YamlErrorImpl::Other(AnyhowError::new(e)))
     }
 
-    fn convert(e: &SerdeYamlError, path: &Path) -> Self {
+    fn convert(e: &SerdeYamlError, path: &Path, contents: &str) -> Self {
         Self(YamlErrorImpl::Syntax {
             message: e.to_string(),
             location: e.location(),
             path: path.to_path_buf(),
+            excerpt: e
+                .location()
+                .and_then(|l| SourceExcerpt::new(contents, l.line())),
         })
     }
+
+    /// Renders the error as a compiler-style diagnostic with a snippet
+    /// of the source when a location is available
+    #[allow(unused)]
+    #[must_use]
+    pub fn display_pretty(&self, use_color: bool) -> String {
+        match self.0 {
+            YamlErrorImpl::Syntax {
+                ref message,
+                ref location,
+                ref path,
+                ref excerpt,
+            } => render_diagnostic(
+                message,
+                Some(path),
+                excerpt.as_ref(),
+                location.as_ref().map(|l| (l.line(), l.column())),
+                use_color,
+            ),
+            YamlErrorImpl::Other(_) => {
+                render_diagnostic(&self.0.to_string(), None, None, None, use_color)
+            }
+        }
+    }
+
+    /// Returns the byte offset into the source text of the line and
+    /// column at which the error occurred, or `None` if the error has no
+    /// location or the source text was not retained
+    #[allow(unused)]
+    #[must_use]
+    pub fn byte_offset(&self) -> Option<usize> {
+        match self.0 {
+            YamlErrorImpl::Syntax {
+                location: Some(ref l),
+                excerpt: Some(ref excerpt),
+                ..
+            } => excerpt.offset(l.line(), l.column()),
+            _ => None,
+        }
+    }
+}
+
+impl HasPosition for YamlError {
+    fn line_col(&self) -> Option<(usize, usize)> {
+        match self.0 {
+            YamlErrorImpl::Syntax {
+                location: Some(ref l),
+                ..
+            } => Some((l.line(), l.column())),
+            _ => None,
+        }
+    }
+
+    fn byte_span(&self) -> Option<Range<usize>> {
+        match self.0 {
+            YamlErrorImpl::Syntax {
+                location: Some(ref l),
+                excerpt: Some(ref excerpt),
+                ..
+            } => Some(excerpt.char_span(l.index())),
+            _ => None,
+        }
+    }
 }
 
 impl HasOtherError for YamlError {
@@ -85,16 +167,25 @@ impl HasOtherError for YamlError {
         self.is_other()
     }
 
-    fn downcast_other_ref<E>(&self) -> Option<&E>
-    where
-        E: Display + Debug + Send + Sync + 'static,
-    {
+    fn downcast_other_any(&self) -> Option<&dyn Any> {
         if let YamlErrorImpl::Other(ref inner) = self.0 {
-            inner.downcast_ref::<E>()
+            Some(inner)
         } else {
             None
         }
     }
+
+    fn downcast_other<E>(self) -> StdResult<E, Self>
+    where
+        E: Display + Debug + Send + Sync + 'static,
+    {
+        match self.0 {
+            YamlErrorImpl::Other(inner) => inner
+                .downcast::<E>()
+                .map_err(|inner| Self(YamlErrorImpl::Other(inner))),
+            inner => Err(Self(inner)),
+        }
+    }
 }
 
 #[derive(Debug, Error)]
@@ -104,29 +195,233 @@ enum YamlErrorImpl {
         message: String,
         location: Option<Location>,
         path: PathBuf,
+        excerpt: Option<SourceExcerpt>,
     },
     #[error(transparent)]
     Other(AnyhowError),
 }
 
+impl PartialEq for YamlErrorImpl {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Syntax { path: a, .. }, Self::Syntax { path: b, .. }) => a == b,
+            (Self::Other(_), Self::Other(_)) => true,
+            _ => false,
+        }
+    }
+}
+
 #[allow(unused)]
-pub fn read_yaml_file<T>(path: &Path) -> StdResult<T, YamlError>
+pub fn read_yaml_file<T>(path: impl AsRef<Path>) -> StdResult<T, YamlError>
 where
     T: DeserializeOwned,
 {
-    let s = read_text_file(path).map_err(YamlError::other)?;
-    let value = serde_yaml::from_str::<T>(&s).map_err(|e| YamlError::convert(&e, path))?;
+    let path = path.as_ref();
+    let s = read_text_file_no_bom(path).map_err(YamlError::other)?;
+    let value = serde_yaml::from_str::<T>(&s).map_err(|e| YamlError::convert(&e, path, &s))?;
     Ok(value)
 }
 
+/// Reads the YAML file at `path` into an untyped value in which custom
+/// tags such as `!MyType` are kept as `Value::Tagged` entries
+///
+/// This is equivalent to `read_yaml_file::<Value>`. Reading into a typed
+/// `T` instead consumes tags by using them to select enum variants
+#[allow(unused)]
+pub fn read_yaml_tagged(path: impl AsRef<Path>) -> StdResult<Value, YamlError> {
+    read_yaml_file::<Value>(path)
+}
+
+/// Reads each `.yaml` and `.yml` file directly inside `dir` into a map
+/// keyed by file stem
+#[allow(unused)]
+pub fn read_yaml_dir<T>(dir: impl AsRef<Path>) -> StdResult<HashMap<String, T>, YamlError>
+where
+    T: DeserializeOwned,
+{
+    let dir = dir.as_ref();
+    let mut paths = Vec::new();
+    for entry in read_dir(dir).map_err(|e| YamlError::other(FileReadError::convert(e, dir)))? {
+        let path = entry
+            .map_err(|e| YamlError::other(FileReadError::convert(e, dir)))?
+            .path();
+        let is_yaml = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| e == "yaml" || e == "yml");
+        if is_yaml && path.is_file() {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    let mut values = HashMap::new();
+    for path in paths {
+        let Some(stem) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        let value = read_yaml_file(&path)?;
+        values.insert(stem.to_string(), value);
+    }
+    Ok(values)
+}
+
+/// Writes `docs` to `path` as a multi-document YAML stream with each
+/// document separated by `---`
+#[allow(unused)]
+pub fn write_yaml_documents<T>(
+    path: impl AsRef<Path>,
+    docs: &[T],
+    overwrite: bool,
+) -> StdResult<(), YamlError>
+where
+    T: Serialize,
+{
+    let path = path.as_ref();
+    let s = docs
+        .iter()
+        .map(serde_yaml::to_string)
+        .collect::<StdResult<Vec<_>, _>>()
+        .map_err(YamlError::other)?
+        .join("---\n");
+    safe_write_file(path, s, overwrite).map_err(YamlError::other)?;
+    Ok(())
+}
+
+/// Writes `value` to `path` as YAML using block style for all non-empty
+/// mappings and sequences, indenting each nesting level by `indent`
+/// spaces
+///
+/// Fails if `indent` is zero or if any mapping key is not a single-line
+/// scalar
+#[allow(unused)]
+pub fn write_yaml_file_styled<T>(
+    path: impl AsRef<Path>,
+    value: &T,
+    indent: usize,
+    overwrite: bool,
+) -> StdResult<(), YamlError>
+where
+    T: Serialize,
+{
+    let path = path.as_ref();
+    if indent < 1 {
+        return Err(YamlError::other(SerdeYamlError::custom(
+            "Indent must be at least 1",
+        )));
+    }
+    let value = serde_yaml::to_value(value).map_err(YamlError::other)?;
+    let mut s = String::new();
+    emit_block(&mut s, &value, indent, 0).map_err(YamlError::other)?;
+    safe_write_file(path, s, overwrite).map_err(YamlError::other)?;
+    Ok(())
+}
+
+fn emit_block(
+    out: &mut String,
+    value: &Value,
+    indent: usize,
+    level: usize,
+) -> StdResult<(), SerdeYamlError> {
+    let pad = " ".repeat(indent * level);
+    match value {
+        Value::Mapping(m) if !m.is_empty() => {
+            for (k, v) in m {
+                out.push_str(&pad);
+                out.push_str(&emit_key(k)?);
+                out.push(':');
+                emit_child(out, v, indent, level)?;
+            }
+        }
+        Value::Sequence(items) if !items.is_empty() => {
+            for item in items {
+                if is_block(item) && indent >= 2 {
+                    // Place the first line of the nested block on the same
+                    // line as the dash, e.g. "-   key: value"
+                    let mut nested = String::new();
+                    emit_block(&mut nested, item, indent, level + 1)?;
+                    out.push_str(&pad);
+                    out.push('-');
+                    out.push_str(&" ".repeat(indent - 1));
+                    out.push_str(&nested[pad.len() + indent..]);
+                } else {
+                    out.push_str(&pad);
+                    out.push('-');
+                    emit_child(out, item, indent, level)?;
+                }
+            }
+        }
+        _ => {
+            out.push_str(&pad);
+            out.push_str(&emit_scalar(value, indent, level)?);
+            out.push('\n');
+        }
+    }
+    Ok(())
+}
+
+fn emit_child(
+    out: &mut String,
+    value: &Value,
+    indent: usize,
+    level: usize,
+) -> StdResult<(), SerdeYamlError> {
+    if is_block(value) {
+        out.push('\n');
+        emit_block(out, value, indent, level + 1)
+    } else {
+        out.push(' ');
+        out.push_str(&emit_scalar(value, indent, level)?);
+        out.push('\n');
+        Ok(())
+    }
+}
+
+fn emit_scalar(value: &Value, indent: usize, level: usize) -> StdResult<String, SerdeYamlError> {
+    // Continuation lines of multi-line scalars must be indented more
+    // deeply than the line that introduces them
+    let continuation_pad = format!("\n{}", " ".repeat(indent * (level + 1)));
+    Ok(serde_yaml::to_string(value)?
+        .trim_end_matches('\n')
+        .replace('\n', &continuation_pad))
+}
+
+fn emit_key(key: &Value) -> StdResult<String, SerdeYamlError> {
+    if matches!(
+        key,
+        Value::Null | Value::Bool(_) | Value::Number(_) | Value::String(_)
+    ) {
+        let s = emit_scalar(key, 0, 0)?;
+        if !s.contains('\n') {
+            return Ok(s);
+        }
+    }
+    Err(SerdeYamlError::custom(
+        "Mapping keys must be single-line scalars",
+    ))
+}
+
+fn is_block(value: &Value) -> bool {
+    match value {
+        Value::Mapping(m) => !m.is_empty(),
+        Value::Sequence(items) => !items.is_empty(),
+        _ => false,
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{read_yaml_file, YamlErrorKind};
-    use crate::error::HasOtherError;
+    use super::{
+        read_yaml_dir, read_yaml_file, read_yaml_tagged, write_yaml_documents,
+        write_yaml_file_styled, YamlErrorKind,
+    };
+    use crate::error::{HasOtherError, HasOtherErrorExt};
     use crate::FileReadError;
     use anyhow::Result;
-    use serde_yaml::Value;
-    use std::fs::write;
+    use rstest::rstest;
+    use serde::Deserialize;
+    use serde_yaml::{Deserializer, Value};
+    use std::fs::{read_to_string, write};
     use tempdir::TempDir;
 
     #[test]
@@ -147,6 +442,41 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_read_yaml_file_with_bom_succeeds() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.yaml");
+        write(&path, "\u{FEFF}message: hello-world")?;
+
+        // Act
+        let value = read_yaml_file::<Value>(&path)?;
+
+        // Assert
+        assert_eq!(Some("hello-world"), value["message"].as_str());
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_yaml_tagged_preserves_tags() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.yaml");
+        write(&path, "plain: aaa\ntagged: !MyType bbb\n")?;
+
+        // Act
+        let value = read_yaml_tagged(&path)?;
+
+        // Assert
+        assert_eq!(Some("aaa"), value["plain"].as_str());
+        let Value::Tagged(tagged) = &value["tagged"] else {
+            panic!("value must be tagged")
+        };
+        assert_eq!("!MyType", tagged.tag.to_string());
+        assert_eq!(Some("bbb"), tagged.value.as_str());
+        Ok(())
+    }
+
     #[test]
     fn test_read_yaml_file_invalid_fails() -> Result<()> {
         // Arrange
@@ -194,4 +524,270 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_read_yaml_file_nonexistent_downcast_other() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.yaml");
+        let Err(e) = read_yaml_file::<Value>(&path) else {
+            panic!("read_yaml_file must fail")
+        };
+
+        // Act
+        let e = e
+            .downcast_other::<FileReadError>()
+            .expect("must be FileReadError");
+
+        // Assert
+        assert!(e.is_not_found());
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_yaml_file_invalid_downcast_other_fails() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.yaml");
+        write(&path, "xxx{\"message\": \"hello-world\"}")?;
+        let Err(e) = read_yaml_file::<Value>(&path) else {
+            panic!("read_yaml_file must fail")
+        };
+
+        // Act
+        let Err(e) = e.downcast_other::<FileReadError>() else {
+            panic!("downcast_other must fail")
+        };
+
+        // Assert
+        assert_eq!(YamlErrorKind::Syntax, e.kind());
+        Ok(())
+    }
+
+    #[test]
+    fn test_display_pretty() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.yaml");
+        write(&path, "aaa: 1\nmessage: - xxx\nbbb: 2\n")?;
+        let Err(e) = read_yaml_file::<Value>(&path) else {
+            panic!("read_yaml_file must fail")
+        };
+
+        // Act
+        let output = e.display_pretty(false);
+
+        // Assert
+        assert!(output.starts_with("error: "));
+        assert!(output.contains(path.to_str().expect("must be valid string")));
+        assert!(output.lines().any(|line| line.trim_end().ends_with('^')));
+        assert!(!output.contains('\x1b'));
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_yaml_dir_succeeds() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        write(temp_dir.path().join("aaa.yaml"), "message: aaa")?;
+        write(temp_dir.path().join("bbb.yml"), "message: bbb")?;
+        write(temp_dir.path().join("ccc.txt"), "xxx{")?;
+
+        // Act
+        let values = read_yaml_dir::<Value>(temp_dir.path())?;
+
+        // Assert
+        assert_eq!(2, values.len());
+        assert_eq!(
+            serde_yaml::from_str::<Value>("message: aaa").expect("must succeed"),
+            values["aaa"]
+        );
+        assert_eq!(
+            serde_yaml::from_str::<Value>("message: bbb").expect("must succeed"),
+            values["bbb"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_yaml_dir_invalid_fails() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        write(temp_dir.path().join("aaa.yaml"), "message: aaa")?;
+        write(temp_dir.path().join("bbb.yml"), "message: bbb")?;
+        let path = temp_dir.path().join("ccc.yaml");
+        write(&path, "xxx{\"message\": \"hello-world\"}")?;
+
+        // Act
+        let Err(e) = read_yaml_dir::<Value>(temp_dir.path()) else {
+            panic!("read_yaml_dir must fail")
+        };
+
+        // Assert
+        assert_eq!(YamlErrorKind::Syntax, e.kind());
+        let message = format!("{e}");
+        assert!(message.contains(path.to_str().expect("must be valid string")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_yaml_documents_succeeds() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.yaml");
+        let docs = [
+            serde_yaml::from_str::<Value>("message: aaa").expect("must succeed"),
+            serde_yaml::from_str::<Value>("message: bbb").expect("must succeed"),
+        ];
+
+        // Act
+        write_yaml_documents(&path, &docs, false)?;
+
+        // Assert
+        let s = read_to_string(&path)?;
+        let values = Deserializer::from_str(&s)
+            .map(Value::deserialize)
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(docs.to_vec(), values);
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_yaml_documents_empty() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.yaml");
+
+        // Act
+        write_yaml_documents::<Value>(&path, &[], false)?;
+
+        // Assert
+        assert_eq!("", read_to_string(&path)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_yaml_file_styled_nested_mapping() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.yaml");
+        let value = serde_yaml::from_str::<Value>(
+            "outer: {inner: {message: hello-world}, items: [aaa, {name: bbb, count: 2}]}",
+        )?;
+
+        // Act
+        write_yaml_file_styled(&path, &value, 4, false)?;
+
+        // Assert
+        let s = read_to_string(&path)?;
+        assert_eq!(
+            "outer:\n    inner:\n        message: hello-world\n    items:\n        - aaa\n        -   name: bbb\n            count: 2\n",
+            s
+        );
+        assert_eq!(value, serde_yaml::from_str::<Value>(&s)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_yaml_file_styled_round_trips() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.yaml");
+        let value = serde_yaml::from_str::<Value>(
+            "a: [[1, 2], [], {}]\nb: \"line0\\nline1\\n\"\nc: [{d: [{e: null}]}]\n",
+        )?;
+
+        // Act
+        write_yaml_file_styled(&path, &value, 3, false)?;
+
+        // Assert
+        let s = read_to_string(&path)?;
+        assert!(!s.contains("[1"));
+        assert_eq!(value, serde_yaml::from_str::<Value>(&s)?);
+        Ok(())
+    }
+
+    #[rstest]
+    #[case(1)]
+    #[case(2)]
+    #[case(4)]
+    fn test_write_yaml_file_styled_nested_mapping_round_trips(#[case] indent: usize) -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.yaml");
+        let value = serde_yaml::from_str::<Value>(
+            "outer: {inner: 1, items: [{a: {b: 2}}, [3]]}\nother: {inner: 4}\n",
+        )?;
+
+        // Act
+        write_yaml_file_styled(&path, &value, indent, false)?;
+
+        // Assert
+        assert_eq!(value, read_yaml_file::<Value>(&path)?);
+        Ok(())
+    }
+
+    #[rstest]
+    #[case(0, "outer: {inner: 1}")]
+    #[case(2, "{[1, 2]: value}")]
+    #[case(2, "{{a: 1}: value}")]
+    #[case(2, "{\"line0\\nline1\": value}")]
+    fn test_write_yaml_file_styled_fails(#[case] indent: usize, #[case] input: &str) -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.yaml");
+        let value = serde_yaml::from_str::<Value>(input)?;
+
+        // Act
+        let Err(e) = write_yaml_file_styled(&path, &value, indent, false) else {
+            panic!("write_yaml_file_styled must fail")
+        };
+
+        // Assert
+        assert!(e.is_other());
+        assert!(!path.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_yaml_error_byte_offset() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.yaml");
+        let s = "message: hello\nkey: é: x\n";
+        write(&path, s)?;
+
+        // Act
+        let Err(e) = read_yaml_file::<Value>(&path) else {
+            panic!("read_yaml_file must fail")
+        };
+
+        // Assert
+        // The column counts characters but the offset counts bytes
+        assert_eq!(Some(22), e.byte_offset());
+        assert_eq!(s.find(": x"), e.byte_offset());
+        Ok(())
+    }
+
+    #[test]
+    fn test_yaml_error_has_position() -> Result<()> {
+        use crate::formats::HasPosition;
+
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.yaml");
+        write(&path, "message: hello\n  - bad: [\n")?;
+
+        // Act
+        let Err(e) = read_yaml_file::<Value>(&path) else {
+            panic!("read_yaml_file must fail")
+        };
+
+        // Assert
+        let (line, _) = e.line_col().expect("must have line and column");
+        assert_eq!(2, line);
+        let span = e.byte_span().expect("must have span");
+        assert!(span.start >= "message: hello\n".len());
+        Ok(())
+    }
 }
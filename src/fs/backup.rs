@@ -19,51 +19,310 @@
 // OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION
 // WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
 //
+use super::hash::file_sha256;
 use super::paths::{file_name_safe_timestamp, label_file_name};
-use chrono::{DateTime, Utc};
-use std::fs::{copy, OpenOptions};
-use std::io::{ErrorKind as IOErrorKind, Result as IOResult};
+use super::read::FileReadError;
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+#[cfg(feature = "flate2")]
+use flate2::{write::GzEncoder, Compression};
+use std::fs::{canonicalize, copy, read_dir, remove_file, File, OpenOptions};
+#[cfg(feature = "flate2")]
+use std::io::copy as io_copy;
+use std::io::{Error as IOError, ErrorKind as IOErrorKind, Result as IOResult};
 use std::path::{Path, PathBuf};
+use std::result::Result as StdResult;
 
+/// Scheme used to label backup file names
+#[allow(unused)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BackupNaming {
+    /// Labels backups with the current time, e.g. `file-20190317T164300000Z.ext`
+    #[default]
+    Timestamp,
+    /// Labels backups with the first free counter, e.g. `file-1.ext`
+    Counter,
+}
+
+/// Description of a backup made by `safe_back_up_info`
+#[allow(unused)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BackupInfo {
+    /// Canonical path of the file that was backed up
+    pub original: PathBuf,
+    /// Path of the backup file
+    pub backup: PathBuf,
+    /// Time used to label the backup file
+    pub created_at: DateTime<Utc>,
+}
+
+/// Copies `path` to a backup file next to it labelled with the current
+/// time and returns the backup's path
+///
+/// Relative paths are resolved against the current directory
 pub fn safe_back_up(path: &Path) -> IOResult<PathBuf> {
-    safe_back_up_inner(path, None)
+    safe_back_up_inner(&canonicalize(path)?, None)
+}
+
+/// Behaves like `safe_back_up` but returns the original path and the
+/// backup's time together with the backup's path
+#[allow(unused)]
+pub fn safe_back_up_info(path: &Path) -> IOResult<BackupInfo> {
+    back_up(&canonicalize(path)?, None)
+}
+
+/// Backs up each of `paths` as `safe_back_up_info` does: if any backup
+/// fails, the backups already made are deleted before the error is
+/// returned
+#[allow(unused)]
+pub fn safe_back_up_many(paths: &[&Path]) -> IOResult<Vec<BackupInfo>> {
+    let mut infos = Vec::with_capacity(paths.len());
+    for path in paths {
+        match safe_back_up_info(path) {
+            Ok(info) => infos.push(info),
+            Err(e) => {
+                for info in &infos {
+                    _ = remove_file(&info.backup);
+                }
+                return Err(e);
+            }
+        }
+    }
+    Ok(infos)
+}
+
+#[allow(unused)]
+pub fn safe_back_up_with(path: &Path, naming: BackupNaming) -> IOResult<PathBuf> {
+    let path = canonicalize(path)?;
+    match naming {
+        BackupNaming::Timestamp => safe_back_up_inner(&path, None),
+        BackupNaming::Counter => safe_back_up_counter(&path),
+    }
+}
+
+/// Copies `path` to a gzip-compressed backup file next to it labelled
+/// with the current time and with `.gz` appended, returning its path
+///
+/// Compression level 0 stores the data uncompressed and level 9 gives
+/// the smallest output at the cost of speed
+#[cfg(feature = "flate2")]
+#[allow(unused)]
+pub fn safe_back_up_compressed(path: &Path, level: Compression) -> IOResult<PathBuf> {
+    let path = canonicalize(path)?;
+    let (file, backup_path) = reserve_backup_file(|| {
+        let mut backup_path = generate_backup_path(&path, &Utc::now())?.into_os_string();
+        backup_path.push(".gz");
+        Ok(PathBuf::from(backup_path))
+    })?;
+
+    let result = File::open(&path).and_then(|mut f| {
+        let mut encoder = GzEncoder::new(file, level);
+        io_copy(&mut f, &mut encoder)?;
+        encoder.finish()
+    });
+    if let Err(e) = result {
+        _ = remove_file(&backup_path);
+        return Err(e);
+    }
+    Ok(backup_path)
+}
+
+/// Calls `safe_back_up_compressed` with the default compression level
+#[cfg(feature = "flate2")]
+#[allow(unused)]
+pub fn safe_back_up_compressed_default(path: &Path) -> IOResult<PathBuf> {
+    safe_back_up_compressed(path, Compression::default())
+}
+
+/// Returns `false` if the newest backup of `path` made by `safe_back_up`
+/// has the same content as `path` and `true` otherwise, including when
+/// there is no backup
+#[allow(unused)]
+pub fn needs_backup(original: &Path) -> StdResult<bool, FileReadError> {
+    let Some(backup_path) = find_newest_backup(original)? else {
+        return Ok(true);
+    };
+    Ok(file_sha256(original)? != file_sha256(&backup_path)?)
+}
+
+/// Deletes backups of `original` made by `safe_back_up` whose embedded
+/// timestamps are older than `max_age` and returns their paths
+///
+/// Backups whose timestamps cannot be parsed are left alone
+#[allow(unused)]
+pub fn prune_backups_older_than(original: &Path, max_age: Duration) -> IOResult<Vec<PathBuf>> {
+    let cutoff = Utc::now() - max_age;
+    let mut removed = Vec::new();
+    for (label, backup_path) in list_backups(original)? {
+        if parse_backup_label(&label).is_some_and(|dt| dt < cutoff) {
+            remove_file(&backup_path)?;
+            removed.push(backup_path);
+        }
+    }
+    Ok(removed)
+}
+
+fn find_newest_backup(path: &Path) -> StdResult<Option<PathBuf>, FileReadError> {
+    let backups = list_backups(path).map_err(|e| FileReadError::convert(e, backup_dir(path)))?;
+    Ok(backups
+        .into_iter()
+        .filter(|(label, _)| is_backup_label(label))
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, p)| p))
 }
 
-fn generate_backup_path(path: &Path, dt: &DateTime<Utc>) -> PathBuf {
-    assert!(path.is_file() && path.is_absolute());
+// Returns the label and path of each file in the directory of path whose
+// name has the form <stem>-<label>.<ext>
+fn list_backups(path: &Path) -> IOResult<Vec<(String, PathBuf)>> {
+    let Some(stem) = path.file_stem().and_then(|s| s.to_str()) else {
+        return Ok(Vec::new());
+    };
+    let prefix = format!("{stem}-");
+    let suffix = path
+        .extension()
+        .and_then(|s| s.to_str())
+        .map(|s| format!(".{s}"))
+        .unwrap_or_default();
 
-    let label = file_name_safe_timestamp(dt);
-    label_file_name(path, &label).expect("must succeed")
+    let mut backups = Vec::new();
+    for entry in read_dir(backup_dir(path))? {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        if let Some(label) = file_name
+            .to_str()
+            .and_then(|s| s.strip_prefix(&prefix))
+            .and_then(|s| s.strip_suffix(&suffix))
+        {
+            backups.push((label.to_string(), entry.path()));
+        }
+    }
+
+    Ok(backups)
+}
+
+fn backup_dir(path: &Path) -> &Path {
+    match path.parent() {
+        Some(d) if !d.as_os_str().is_empty() => d,
+        _ => Path::new("."),
+    }
+}
+
+fn parse_backup_label(s: &str) -> Option<DateTime<Utc>> {
+    if !is_backup_label(s) {
+        return None;
+    }
+    NaiveDateTime::parse_from_str(s, "%Y%m%dT%H%M%S%3fZ")
+        .ok()
+        .map(|dt| dt.and_utc())
+}
+
+// Matches labels generated by file_name_safe_timestamp such as
+// 20190317T164300000Z
+fn is_backup_label(s: &str) -> bool {
+    s.len() == 19
+        && s.bytes().enumerate().all(|(i, b)| match i {
+            8 => b == b'T',
+            18 => b == b'Z',
+            _ => b.is_ascii_digit(),
+        })
+}
+
+/// Returns the path of the backup of `path` that `safe_back_up` would
+/// create at time `dt`, or `None` if `path` is not an absolute path to an
+/// existing file
+#[allow(unused)]
+#[must_use]
+pub fn backup_path_for(path: &Path, dt: &DateTime<Utc>) -> Option<PathBuf> {
+    if !(path.is_file() && path.is_absolute()) {
+        return None;
+    }
+
+    label_file_name(path, &file_name_safe_timestamp(dt))
+}
+
+fn generate_backup_path(path: &Path, dt: &DateTime<Utc>) -> IOResult<PathBuf> {
+    backup_path_for(path, dt).ok_or_else(|| cannot_back_up(path))
+}
+
+fn cannot_back_up(path: &Path) -> IOError {
+    IOError::new(
+        IOErrorKind::InvalidInput,
+        format!("Cannot back up {}", path.display()),
+    )
 }
 
 fn safe_back_up_inner(path: &Path, now: Option<DateTime<Utc>>) -> IOResult<PathBuf> {
-    assert!(path.is_file() && path.is_absolute());
+    Ok(back_up(path, now)?.backup)
+}
 
-    let mut backup_path = generate_backup_path(path, &now.unwrap_or_else(Utc::now));
+fn back_up(path: &Path, now: Option<DateTime<Utc>>) -> IOResult<BackupInfo> {
+    let mut now = now;
+    let mut created_at = Utc::now();
+    let (_, backup_path) = reserve_backup_file(|| {
+        created_at = now.take().unwrap_or_else(Utc::now);
+        generate_backup_path(path, &created_at)
+    })?;
+
+    copy_to_reserved(path, &backup_path)?;
+    Ok(BackupInfo {
+        original: path.to_path_buf(),
+        backup: backup_path,
+        created_at,
+    })
+}
+
+fn safe_back_up_counter(path: &Path) -> IOResult<PathBuf> {
+    if !path.is_file() {
+        return Err(cannot_back_up(path));
+    }
+
+    let mut counter = 0usize;
+    let (_, backup_path) = reserve_backup_file(|| {
+        counter += 1;
+        label_file_name(path, &counter.to_string()).ok_or_else(|| cannot_back_up(path))
+    })?;
+
+    copy_to_reserved(path, &backup_path)?;
+    Ok(backup_path)
+}
+
+// Creates the first of the paths returned by next_path that does not
+// already exist, returning the new empty file and its path
+fn reserve_backup_file<F>(mut next_path: F) -> IOResult<(File, PathBuf)>
+where
+    F: FnMut() -> IOResult<PathBuf>,
+{
     loop {
+        let backup_path = next_path()?;
         match OpenOptions::new()
             .write(true)
             .create_new(true)
             .open(&backup_path)
         {
-            Ok(_) => break,
-            Err(e) if e.kind() == IOErrorKind::AlreadyExists => {
-                backup_path = generate_backup_path(path, &Utc::now());
-            }
+            Ok(file) => return Ok((file, backup_path)),
+            Err(e) if e.kind() == IOErrorKind::AlreadyExists => {}
             Err(e) => return Err(e),
         }
     }
+}
 
-    copy(path, &backup_path)?;
-    Ok(backup_path)
+// Copies path over the file reserved at backup_path, removing the
+// reserved file if the copy fails
+fn copy_to_reserved(path: &Path, backup_path: &Path) -> IOResult<()> {
+    if let Err(e) = copy(path, backup_path) {
+        _ = remove_file(backup_path);
+        return Err(e);
+    }
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::fs::WorkingDirectory;
     use anyhow::Result;
     use chrono::{TimeZone, Utc};
-    use std::fs::{read_dir, read_to_string, write};
+    use serial_test::serial;
+    use std::fs::{create_dir, read_dir, read_to_string, write};
     use tempdir::TempDir;
 
     #[test]
@@ -120,4 +379,263 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn needs_backup_no_backup() -> Result<()> {
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.ext");
+        write(&path, "CONTENT")?;
+
+        assert!(needs_backup(&path)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn needs_backup_identical() -> Result<()> {
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.ext");
+        write(&path, "CONTENT1")?;
+        safe_back_up_inner(
+            &path,
+            Some(
+                Utc.with_ymd_and_hms(2019, 3, 17, 16, 43, 0)
+                    .single()
+                    .expect("must be valid"),
+            ),
+        )?;
+        write(&path, "CONTENT2")?;
+        safe_back_up_inner(
+            &path,
+            Some(
+                Utc.with_ymd_and_hms(2020, 3, 17, 16, 43, 0)
+                    .single()
+                    .expect("must be valid"),
+            ),
+        )?;
+
+        assert!(!needs_backup(&path)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn needs_backup_modified() -> Result<()> {
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.ext");
+        write(&path, "CONTENT1")?;
+        safe_back_up(&path)?;
+        write(&path, "CONTENT2")?;
+
+        assert!(needs_backup(&path)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn counter() -> Result<()> {
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.ext");
+
+        let mut output_paths = Vec::new();
+        for content in ["CONTENT1", "CONTENT2", "CONTENT3"] {
+            write(&path, content)?;
+            output_paths.push(safe_back_up_with(&path, BackupNaming::Counter)?);
+        }
+
+        assert_eq!(
+            vec![
+                temp_dir.path().join("file-1.ext"),
+                temp_dir.path().join("file-2.ext"),
+                temp_dir.path().join("file-3.ext"),
+            ],
+            output_paths
+        );
+        assert_eq!("CONTENT1", read_to_string(&output_paths[0])?);
+        assert_eq!("CONTENT2", read_to_string(&output_paths[1])?);
+        assert_eq!("CONTENT3", read_to_string(&output_paths[2])?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn prune_older_than() -> Result<()> {
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.ext");
+        write(&path, "CONTENT")?;
+        let old_path = safe_back_up_inner(
+            &path,
+            Some(
+                Utc.with_ymd_and_hms(2019, 3, 17, 16, 43, 0)
+                    .single()
+                    .expect("must be valid"),
+            ),
+        )?;
+        let fresh_path = safe_back_up(&path)?;
+
+        // Month 13 cannot be parsed
+        let invalid_path = temp_dir.path().join("file-20191317T164300000Z.ext");
+        write(&invalid_path, "CONTENT")?;
+
+        let removed = prune_backups_older_than(&path, Duration::days(30))?;
+
+        assert_eq!(vec![old_path.clone()], removed);
+        assert!(!old_path.exists());
+        assert!(fresh_path.exists());
+        assert!(invalid_path.exists());
+        assert!(path.exists());
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn relative_path() -> Result<()> {
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.ext");
+        write(&path, "CONTENT")?;
+        let working_dir = WorkingDirectory::change(temp_dir.path())?;
+
+        let output_path = safe_back_up(Path::new("file.ext"));
+        drop(working_dir);
+
+        let output_path = output_path?;
+        assert!(output_path.is_absolute());
+        assert_ne!(path, output_path);
+        assert_eq!("CONTENT", read_to_string(output_path)?);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "flate2")]
+    #[test]
+    fn compressed_level() -> Result<()> {
+        use flate2::read::GzDecoder;
+        use std::fs::{metadata, File};
+        use std::io::Read;
+
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.ext");
+        let content = "line 0\nline 1\nline 2\n".repeat(10_000);
+        write(&path, &content)?;
+
+        let fast_path = safe_back_up_compressed(&path, Compression::fast())?;
+        let best_path = safe_back_up_compressed(&path, Compression::best())?;
+
+        assert_ne!(fast_path, best_path);
+        assert!(metadata(&best_path)?.len() <= metadata(&fast_path)?.len());
+        let mut s = String::new();
+        GzDecoder::new(File::open(&best_path)?).read_to_string(&mut s)?;
+        assert_eq!(content, s);
+
+        Ok(())
+    }
+
+    #[test]
+    fn info() -> Result<()> {
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.ext");
+        write(&path, "CONTENT")?;
+
+        let info = safe_back_up_info(&path)?;
+
+        assert_eq!(canonicalize(&path)?, info.original);
+        assert_eq!(
+            Some(info.backup.clone()),
+            label_file_name(&info.original, &file_name_safe_timestamp(&info.created_at))
+        );
+        assert_eq!("CONTENT", read_to_string(&info.backup)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn relative_path_inner_fails() {
+        let dt = Utc
+            .with_ymd_and_hms(2019, 3, 17, 16, 43, 0)
+            .single()
+            .expect("must be valid");
+
+        assert_eq!(None, backup_path_for(Path::new("file.ext"), &dt));
+        let Err(e) = safe_back_up_inner(Path::new("file.ext"), Some(dt)) else {
+            panic!("safe_back_up_inner must fail")
+        };
+        assert_eq!(IOErrorKind::InvalidInput, e.kind());
+    }
+
+    #[test]
+    fn reserve_skips_existing() -> Result<()> {
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path1 = temp_dir.path().join("file-1.ext");
+        let path2 = temp_dir.path().join("file-2.ext");
+        write(&path1, "CONTENT")?;
+        let mut candidates = vec![path2.clone(), path1.clone()];
+
+        let (_, backup_path) =
+            reserve_backup_file(|| Ok(candidates.pop().expect("must have candidate")))?;
+
+        assert_eq!(path2, backup_path);
+        assert_eq!("CONTENT", read_to_string(&path1)?);
+        assert_eq!("", read_to_string(&path2)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn counter_directory_fails() -> Result<()> {
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("dir");
+        create_dir(&path)?;
+
+        let Err(e) = safe_back_up_with(&path, BackupNaming::Counter) else {
+            panic!("safe_back_up_with must fail")
+        };
+
+        assert_eq!(IOErrorKind::InvalidInput, e.kind());
+        let items = read_dir(temp_dir.path())?
+            .map(|e| e.map(|e| e.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(vec![path], items);
+
+        Ok(())
+    }
+
+    #[test]
+    fn many() -> Result<()> {
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path1 = temp_dir.path().join("file1.ext");
+        let path2 = temp_dir.path().join("file2.ext");
+        write(&path1, "CONTENT1")?;
+        write(&path2, "CONTENT2")?;
+
+        let infos = safe_back_up_many(&[&path1, &path2])?;
+
+        assert_eq!(2, infos.len());
+        assert_eq!(canonicalize(&path1)?, infos[0].original);
+        assert_eq!(canonicalize(&path2)?, infos[1].original);
+        assert_eq!("CONTENT1", read_to_string(&infos[0].backup)?);
+        assert_eq!("CONTENT2", read_to_string(&infos[1].backup)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn many_missing_cleans_up() -> Result<()> {
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path1 = temp_dir.path().join("file1.ext");
+        let path2 = temp_dir.path().join("file2.ext");
+        write(&path1, "CONTENT1")?;
+
+        let Err(e) = safe_back_up_many(&[&path1, &path2]) else {
+            panic!("safe_back_up_many must fail")
+        };
+
+        assert_eq!(IOErrorKind::NotFound, e.kind());
+        let items = read_dir(temp_dir.path())?
+            .map(|e| e.map(|e| e.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(vec![path1], items);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,324 @@
+// Copyright (c) 2023 Richard Cook
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the
+// "Software"), to deal in the Software without restriction, including
+// without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to
+// permit persons to whom the Software is furnished to do so, subject to
+// the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE
+// LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION
+// WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+//
+use super::hash::file_sha256;
+use super::read::{open_file, FileReadError};
+use super::write::{safe_create_file, FileWriteError};
+use std::fs::metadata;
+use std::io::{Error as IOError, ErrorKind as IOErrorKind, Read, Write};
+use std::path::Path;
+use std::result::Result as StdResult;
+use std::time::SystemTime;
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Copies `src` to `dst` in chunks, calling `progress` with the number
+/// of bytes copied so far and the total size after each chunk
+#[allow(unused)]
+pub fn copy_file_with_progress<F>(
+    src: impl AsRef<Path>,
+    dst: impl AsRef<Path>,
+    overwrite: bool,
+    mut progress: F,
+) -> StdResult<u64, FileWriteError>
+where
+    F: FnMut(u64, u64),
+{
+    let src = src.as_ref();
+    let dst = dst.as_ref();
+    let read_error = |e| FileWriteError::convert_read(FileReadError::convert(e, src));
+    let mut reader = open_file(src).map_err(FileWriteError::convert_read)?;
+    let total = reader.metadata().map_err(read_error)?.len();
+    let mut writer = safe_create_file(dst, overwrite)?;
+    writer
+        .set_len(0)
+        .map_err(|e| FileWriteError::convert(e, dst))?;
+
+    let mut buffer = vec![0; CHUNK_SIZE];
+    let mut copied = 0;
+    loop {
+        let n = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == IOErrorKind::Interrupted => continue,
+            Err(e) => return Err(read_error(e)),
+        };
+        writer
+            .write_all(&buffer[..n])
+            .map_err(|e| FileWriteError::convert(e, dst))?;
+        copied += n as u64;
+        progress(copied, total);
+    }
+
+    Ok(copied)
+}
+
+/// Copies `src` to `dst` if `dst` is missing or older than `src`
+///
+/// Copies unconditionally if either modification time is unavailable.
+/// Returns `true` if the file was copied
+#[allow(unused)]
+pub fn copy_if_newer(
+    src: impl AsRef<Path>,
+    dst: impl AsRef<Path>,
+) -> StdResult<bool, FileWriteError> {
+    fn modified(path: &Path) -> Option<SystemTime> {
+        metadata(path).and_then(|m| m.modified()).ok()
+    }
+
+    let src = src.as_ref();
+    let dst = dst.as_ref();
+    if let (Some(src_modified), Some(dst_modified)) = (modified(src), modified(dst)) {
+        if src_modified <= dst_modified {
+            return Ok(false);
+        }
+    }
+
+    copy_file_with_progress(src, dst, true, |_, _| {})?;
+    Ok(true)
+}
+
+/// Copies `src` to `dst` and then checks that the SHA-256 digests of the
+/// two files match, returning the hex-encoded digest
+#[allow(unused)]
+pub fn copy_file_verified(
+    src: impl AsRef<Path>,
+    dst: impl AsRef<Path>,
+    overwrite: bool,
+) -> StdResult<String, FileWriteError> {
+    let src = src.as_ref();
+    let dst = dst.as_ref();
+    copy_file_with_progress(src, dst, overwrite, |_, _| {})?;
+    let src_digest = file_sha256(src).map_err(FileWriteError::convert_read)?;
+    let dst_digest = file_sha256(dst).map_err(FileWriteError::convert_read)?;
+    if src_digest != dst_digest {
+        return Err(FileWriteError::other(IOError::new(
+            IOErrorKind::InvalidData,
+            format!(
+                "Copy {} of {} has SHA-256 digest {dst_digest} but expected {src_digest}",
+                dst.display(),
+                src.display()
+            ),
+        )));
+    }
+    Ok(dst_digest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{copy_file_verified, copy_file_with_progress, copy_if_newer, CHUNK_SIZE};
+    use crate::fs::{file_sha256, FileWriteErrorKind};
+    use anyhow::Result;
+    use std::fs::{read, write, File};
+    use std::path::Path;
+    use std::time::{Duration, SystemTime};
+    use tempdir::TempDir;
+
+    fn set_modified(path: &Path, age: Duration) -> Result<()> {
+        File::options()
+            .write(true)
+            .open(path)?
+            .set_modified(SystemTime::now() - age)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_file_with_progress_succeeds() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let src = temp_dir.path().join("src.bin");
+        let dst = temp_dir.path().join("aaa").join("dst.bin");
+        let contents = (0..=255u8)
+            .cycle()
+            .take(CHUNK_SIZE * 2 + 100)
+            .collect::<Vec<_>>();
+        write(&src, &contents)?;
+        let mut calls = Vec::new();
+
+        // Act
+        let count = copy_file_with_progress(&src, &dst, false, |copied, total| {
+            calls.push((copied, total));
+        })?;
+
+        // Assert
+        let size = contents.len() as u64;
+        assert_eq!(size, count);
+        assert_eq!(Some(&(size, size)), calls.last());
+        assert!(calls.windows(2).all(|w| w[0].0 < w[1].0));
+        assert_eq!(contents, read(&dst)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_file_with_progress_overwrite_succeeds() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let src = temp_dir.path().join("src.txt");
+        let dst = temp_dir.path().join("dst.txt");
+        write(&src, "hello-world")?;
+        write(&dst, "something-much-longer")?;
+
+        // Act
+        let count = copy_file_with_progress(&src, &dst, true, |_, _| {})?;
+
+        // Assert
+        assert_eq!(11, count);
+        assert_eq!(b"hello-world".to_vec(), read(&dst)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_file_with_progress_exists_no_overwrite_fails() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let src = temp_dir.path().join("src.txt");
+        let dst = temp_dir.path().join("dst.txt");
+        write(&src, "hello-world")?;
+        write(&dst, "something-else")?;
+
+        // Act
+        let Err(e) = copy_file_with_progress(&src, &dst, false, |_, _| {}) else {
+            panic!("copy_file_with_progress must fail")
+        };
+
+        // Assert
+        assert_eq!(FileWriteErrorKind::AlreadyExists, e.kind());
+        assert_eq!(b"something-else".to_vec(), read(&dst)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_file_with_progress_missing_source_fails() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let src = temp_dir.path().join("src.txt");
+        let dst = temp_dir.path().join("dst.txt");
+
+        // Act
+        let Err(e) = copy_file_with_progress(&src, &dst, false, |_, _| {}) else {
+            panic!("copy_file_with_progress must fail")
+        };
+
+        // Assert
+        assert!(e.is_not_found());
+        assert!(format!("{e}").contains(src.to_str().expect("must be valid string")));
+        assert!(!dst.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_if_newer_newer_source_copies() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let src = temp_dir.path().join("src.txt");
+        let dst = temp_dir.path().join("dst.txt");
+        write(&src, "hello-world")?;
+        write(&dst, "something-else")?;
+        set_modified(&src, Duration::from_secs(10))?;
+        set_modified(&dst, Duration::from_secs(30))?;
+
+        // Act
+        let copied = copy_if_newer(&src, &dst)?;
+
+        // Assert
+        assert!(copied);
+        assert_eq!(b"hello-world".to_vec(), read(&dst)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_if_newer_older_source_skips() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let src = temp_dir.path().join("src.txt");
+        let dst = temp_dir.path().join("dst.txt");
+        write(&src, "hello-world")?;
+        write(&dst, "something-else")?;
+        set_modified(&src, Duration::from_secs(30))?;
+        set_modified(&dst, Duration::from_secs(10))?;
+
+        // Act
+        let copied = copy_if_newer(&src, &dst)?;
+
+        // Assert
+        assert!(!copied);
+        assert_eq!(b"something-else".to_vec(), read(&dst)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_if_newer_missing_destination_copies() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let src = temp_dir.path().join("src.txt");
+        let dst = temp_dir.path().join("aaa").join("dst.txt");
+        write(&src, "hello-world")?;
+
+        // Act
+        let copied = copy_if_newer(&src, &dst)?;
+
+        // Assert
+        assert!(copied);
+        assert_eq!(b"hello-world".to_vec(), read(&dst)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_file_verified_succeeds() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let src = temp_dir.path().join("src.bin");
+        let dst = temp_dir.path().join("aaa").join("dst.bin");
+        let contents = (0..=255u8)
+            .cycle()
+            .take(CHUNK_SIZE + 100)
+            .collect::<Vec<_>>();
+        write(&src, &contents)?;
+
+        // Act
+        let digest = copy_file_verified(&src, &dst, false)?;
+
+        // Assert
+        assert_eq!(file_sha256(&src)?, digest);
+        assert_eq!(contents, read(&dst)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_file_verified_exists_no_overwrite_fails() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let src = temp_dir.path().join("src.txt");
+        let dst = temp_dir.path().join("dst.txt");
+        write(&src, "hello-world")?;
+        write(&dst, "something-else")?;
+
+        // Act
+        let Err(e) = copy_file_verified(&src, &dst, false) else {
+            panic!("copy_file_verified must fail")
+        };
+
+        // Assert
+        assert_eq!(FileWriteErrorKind::AlreadyExists, e.kind());
+        assert_eq!(b"something-else".to_vec(), read(&dst)?);
+        Ok(())
+    }
+}
@@ -0,0 +1,86 @@
+// Copyright (c) 2023 Richard Cook
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the
+// "Software"), to deal in the Software without restriction, including
+// without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to
+// permit persons to whom the Software is furnished to do so, subject to
+// the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE
+// LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION
+// WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+//
+use super::read::{read_text_file, FileReadError};
+use similar::TextDiff;
+use std::path::Path;
+use std::result::Result as StdResult;
+
+/// Returns a unified diff of the text files `a` and `b` using their paths
+/// as headers, or an empty string if the files are identical
+#[allow(unused)]
+pub fn text_file_diff(a: &Path, b: &Path) -> StdResult<String, FileReadError> {
+    let a_text = read_text_file(a)?;
+    let b_text = read_text_file(b)?;
+    Ok(TextDiff::from_lines(&a_text, &b_text)
+        .unified_diff()
+        .header(&a.display().to_string(), &b.display().to_string())
+        .to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::text_file_diff;
+    use anyhow::Result;
+    use std::fs::write;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_text_file_diff_identical() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let a = temp_dir.path().join("a.txt");
+        let b = temp_dir.path().join("b.txt");
+        write(&a, "aaa\nbbb\n")?;
+        write(&b, "aaa\nbbb\n")?;
+
+        // Act
+        let diff = text_file_diff(&a, &b)?;
+
+        // Assert
+        assert_eq!("", diff);
+        Ok(())
+    }
+
+    #[test]
+    fn test_text_file_diff_differing() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let a = temp_dir.path().join("a.txt");
+        let b = temp_dir.path().join("b.txt");
+        write(&a, "aaa\nbbb\nccc\n")?;
+        write(&b, "aaa\nBBB\nccc\n")?;
+
+        // Act
+        let diff = text_file_diff(&a, &b)?;
+
+        // Assert
+        assert_eq!(
+            format!(
+                "--- {}\n+++ {}\n@@ -1,3 +1,3 @@\n aaa\n-bbb\n+BBB\n ccc\n",
+                a.display(),
+                b.display()
+            ),
+            diff
+        );
+        Ok(())
+    }
+}
@@ -19,8 +19,39 @@
 // OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION
 // WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
 //
+use std::borrow::Cow;
+use std::fs::read_dir;
 use std::path::{Path, PathBuf};
 
+/// Number of directories, starting with the start directory, searched by
+/// `find_sentinel_dir` and `find_sentinel_file` when no limit is given
+pub const DEFAULT_SENTINEL_LIMIT: i32 = 30;
+
+/// How the sentinel search ascends from the start directory
+#[allow(unused)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum AncestorWalk {
+    /// Ascends the start directory as given using [`Path::parent`] so that
+    /// a symlinked start directory is treated as living in the directory
+    /// containing the symlink: this is the behaviour of `find_sentinel_dir`
+    /// and `find_sentinel_file`
+    #[default]
+    Lexical,
+
+    /// Resolves symlinks in the start directory first and ascends the
+    /// real path on disk
+    Resolved,
+}
+
+impl AncestorWalk {
+    fn start_dir(self, start_dir: &Path) -> Option<Cow<'_, Path>> {
+        match self {
+            Self::Lexical => Some(Cow::Borrowed(start_dir)),
+            Self::Resolved => start_dir.canonicalize().ok().map(Cow::Owned),
+        }
+    }
+}
+
 #[allow(unused)]
 #[must_use]
 pub fn find_sentinel_dir(
@@ -29,7 +60,7 @@ pub fn find_sentinel_dir(
     limit: Option<i32>,
 ) -> Option<PathBuf> {
     let mut dir = start_dir;
-    let mut count = limit.unwrap_or(30);
+    let mut count = limit.unwrap_or(DEFAULT_SENTINEL_LIMIT);
     loop {
         if count == 0 {
             return None;
@@ -49,6 +80,19 @@ pub fn find_sentinel_dir(
     }
 }
 
+/// Finds the sentinel directory ascending from `start_dir` as specified
+/// by `walk`
+#[allow(unused)]
+#[must_use]
+pub fn find_sentinel_dir_with_walk(
+    sentinel_name: &Path,
+    start_dir: &Path,
+    limit: Option<i32>,
+    walk: AncestorWalk,
+) -> Option<PathBuf> {
+    find_sentinel_dir(sentinel_name, &walk.start_dir(start_dir)?, limit)
+}
+
 #[allow(unused)]
 #[must_use]
 pub fn find_sentinel_file(
@@ -56,8 +100,66 @@ pub fn find_sentinel_file(
     start_dir: &Path,
     limit: Option<i32>,
 ) -> Option<PathBuf> {
+    find_sentinel_file_with_depth(sentinel_name, start_dir, limit).map(|(path, _)| path)
+}
+
+/// Finds the sentinel file ascending from `start_dir` as specified by
+/// `walk`
+#[allow(unused)]
+#[must_use]
+pub fn find_sentinel_file_with_walk(
+    sentinel_name: &Path,
+    start_dir: &Path,
+    limit: Option<i32>,
+    walk: AncestorWalk,
+) -> Option<PathBuf> {
+    find_sentinel_file(sentinel_name, &walk.start_dir(start_dir)?, limit)
+}
+
+/// Finds the sentinel file and returns its path along with the number of
+/// parent directories ascended from `start_dir` to find it
+#[allow(unused)]
+#[must_use]
+pub fn find_sentinel_file_with_depth(
+    sentinel_name: &Path,
+    start_dir: &Path,
+    limit: Option<i32>,
+) -> Option<(PathBuf, usize)> {
     let mut dir = start_dir;
-    let mut count = limit.unwrap_or(30);
+    let mut count = limit.unwrap_or(DEFAULT_SENTINEL_LIMIT);
+    let mut depth = 0;
+    loop {
+        if count == 0 {
+            return None;
+        }
+
+        let sentinel_file_path = dir.join(sentinel_name);
+        if sentinel_file_path.is_file() {
+            return Some((sentinel_file_path, depth));
+        }
+
+        match dir.parent() {
+            Some(d) => dir = d,
+            None => return None,
+        }
+
+        count -= 1;
+        depth += 1;
+    }
+}
+
+/// Finds the sentinel file matching `sentinel_name` case-insensitively and
+/// returns its path as it exists on disk, preferring an exact match
+#[allow(unused)]
+#[must_use]
+pub fn find_sentinel_file_ci(
+    sentinel_name: &str,
+    start_dir: &Path,
+    limit: Option<i32>,
+) -> Option<PathBuf> {
+    let sentinel_name_lower = sentinel_name.to_lowercase();
+    let mut dir = start_dir;
+    let mut count = limit.unwrap_or(DEFAULT_SENTINEL_LIMIT);
     loop {
         if count == 0 {
             return None;
@@ -68,6 +170,23 @@ pub fn find_sentinel_file(
             return Some(sentinel_file_path);
         }
 
+        if let Ok(entries) = read_dir(dir) {
+            let mut paths = entries
+                .filter_map(Result::ok)
+                .filter(|e| {
+                    e.file_name()
+                        .to_str()
+                        .is_some_and(|n| n.to_lowercase() == sentinel_name_lower)
+                })
+                .map(|e| e.path())
+                .filter(|p| p.is_file())
+                .collect::<Vec<_>>();
+            paths.sort();
+            if let Some(path) = paths.into_iter().next() {
+                return Some(path);
+            }
+        }
+
         match dir.parent() {
             Some(d) => dir = d,
             None => return None,
@@ -77,9 +196,53 @@ pub fn find_sentinel_file(
     }
 }
 
+/// Finds the sentinel file without ascending past the file system
+/// containing `start_dir`, such as a network mount
+#[cfg(unix)]
+#[allow(unused)]
+#[must_use]
+pub fn find_sentinel_file_same_device(sentinel_name: &Path, start_dir: &Path) -> Option<PathBuf> {
+    use std::os::unix::fs::MetadataExt;
+
+    let start_dev = start_dir.metadata().ok()?.dev();
+    let mut dir = start_dir;
+    let mut count = DEFAULT_SENTINEL_LIMIT;
+    loop {
+        if count == 0 {
+            return None;
+        }
+
+        let sentinel_file_path = dir.join(sentinel_name);
+        if sentinel_file_path.is_file() {
+            return Some(sentinel_file_path);
+        }
+
+        match dir.parent() {
+            Some(d) if is_same_device(start_dev, d) => dir = d,
+            _ => return None,
+        }
+
+        count -= 1;
+    }
+}
+
+#[cfg(unix)]
+fn is_same_device(dev: u64, path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    path.metadata().is_ok_and(|m| m.dev() == dev)
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{find_sentinel_dir, find_sentinel_file};
+    use super::{
+        find_sentinel_dir, find_sentinel_file, find_sentinel_file_ci,
+        find_sentinel_file_with_depth, DEFAULT_SENTINEL_LIMIT,
+    };
+    #[cfg(unix)]
+    use super::{
+        find_sentinel_file_same_device, find_sentinel_file_with_walk, is_same_device, AncestorWalk,
+    };
     use anyhow::Result;
     use std::fs::create_dir_all;
     use std::fs::write;
@@ -149,4 +312,143 @@ mod tests {
         assert!(value.is_none());
         Ok(())
     }
+
+    #[test]
+    fn test_find_sentinel_dir_default_limit() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let mut start_dir = temp_dir.path().to_path_buf();
+        for _ in 0..DEFAULT_SENTINEL_LIMIT {
+            start_dir.push("d");
+        }
+        create_dir_all(&start_dir)?;
+        let within_limit_path = temp_dir.path().join("d").join("WITHIN");
+        let beyond_limit_path = temp_dir.path().join("BEYOND");
+        create_dir_all(&within_limit_path)?;
+        create_dir_all(&beyond_limit_path)?;
+
+        // Act
+        let within_limit = find_sentinel_dir(Path::new("WITHIN"), &start_dir, None);
+        let beyond_limit = find_sentinel_dir(Path::new("BEYOND"), &start_dir, None);
+
+        // Assert
+        assert_eq!(Some(within_limit_path), within_limit);
+        assert!(beyond_limit.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_sentinel_file_with_depth_found() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let start_dir = temp_dir.path().join("aaa").join("bbb").join("ccc");
+        let sentinel_file_path = temp_dir.path().join("aaa").join("SENTINEL");
+        create_dir_all(&start_dir)?;
+        write(&sentinel_file_path, "CONTENTS")?;
+
+        // Act
+        let value = find_sentinel_file_with_depth(Path::new("SENTINEL"), &start_dir, Some(3));
+
+        // Asset
+        assert_eq!(Some((sentinel_file_path, 2)), value);
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_sentinel_file_ci_found() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let start_dir = temp_dir.path().join("aaa").join("bbb").join("ccc");
+        let sentinel_file_path = temp_dir.path().join("aaa").join("Config.toml");
+        create_dir_all(&start_dir)?;
+        write(&sentinel_file_path, "CONTENTS")?;
+
+        // Act
+        let value = find_sentinel_file_ci("config.toml", &start_dir, Some(3));
+
+        // Asset
+        assert_eq!(Some(sentinel_file_path), value);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_find_sentinel_file_with_walk_symlink() -> Result<()> {
+        use std::os::unix::fs::symlink;
+
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let real_dir = temp_dir.path().join("real").join("aaa");
+        let link_parent_dir = temp_dir.path().join("link");
+        let start_dir = link_parent_dir.join("aaa");
+        create_dir_all(&real_dir)?;
+        create_dir_all(&link_parent_dir)?;
+        symlink(&real_dir, &start_dir)?;
+        let real_sentinel_file_path = temp_dir.path().join("real").join("SENTINEL");
+        let link_sentinel_file_path = link_parent_dir.join("SENTINEL");
+        write(&real_sentinel_file_path, "CONTENTS")?;
+        write(&link_sentinel_file_path, "CONTENTS")?;
+
+        // Act
+        let lexical = find_sentinel_file_with_walk(
+            Path::new("SENTINEL"),
+            &start_dir,
+            Some(3),
+            AncestorWalk::Lexical,
+        );
+        let resolved = find_sentinel_file_with_walk(
+            Path::new("SENTINEL"),
+            &start_dir,
+            Some(3),
+            AncestorWalk::Resolved,
+        );
+
+        // Asset
+        assert_eq!(Some(link_sentinel_file_path), lexical);
+        assert_eq!(Some(real_sentinel_file_path.canonicalize()?), resolved);
+        assert_eq!(
+            lexical,
+            find_sentinel_file(Path::new("SENTINEL"), &start_dir, Some(3))
+        );
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_find_sentinel_file_same_device_found() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let start_dir = temp_dir.path().join("aaa").join("bbb").join("ccc");
+        let sentinel_file_path = temp_dir.path().join("aaa").join("SENTINEL");
+        create_dir_all(&start_dir)?;
+        write(&sentinel_file_path, "CONTENTS")?;
+
+        // Act
+        let value = find_sentinel_file_same_device(Path::new("SENTINEL"), &start_dir);
+
+        // Asset
+        assert_eq!(Some(sentinel_file_path), value);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_is_same_device() -> Result<()> {
+        use std::os::unix::fs::MetadataExt;
+
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let dev = temp_dir.path().metadata()?.dev();
+
+        // Act
+        let same = is_same_device(dev, temp_dir.path());
+        let different = is_same_device(dev.wrapping_add(1), temp_dir.path());
+        let nonexistent = is_same_device(dev, &temp_dir.path().join("xxx"));
+
+        // Asset
+        assert!(same);
+        assert!(!different);
+        assert!(!nonexistent);
+        Ok(())
+    }
 }
@@ -0,0 +1,163 @@
+// Copyright (c) 2023 Richard Cook
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the
+// "Software"), to deal in the Software without restriction, including
+// without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to
+// permit persons to whom the Software is furnished to do so, subject to
+// the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE
+// LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION
+// WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+//
+use super::read::{open_file, FileReadError};
+use super::walk::walk_dir;
+use sha2::{Digest, Sha256};
+use std::io::copy;
+use std::path::Path;
+use std::result::Result as StdResult;
+
+/// Returns the hex-encoded SHA-256 digest of the relative paths and
+/// contents of all files under `root`
+///
+/// Files are visited in sorted order of relative path so the digest does
+/// not depend on the order in which the file system returns entries
+#[allow(unused)]
+pub fn hash_dir_tree(root: &Path) -> StdResult<String, FileReadError> {
+    let mut paths = Vec::new();
+    for path in walk_dir(root)? {
+        let path = path?;
+        if path.is_file() {
+            paths.push(path);
+        }
+    }
+
+    let mut entries = paths
+        .into_iter()
+        .map(|path| {
+            let relative_path = path
+                .strip_prefix(root)
+                .unwrap_or(&path)
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            (relative_path, path)
+        })
+        .collect::<Vec<_>>();
+    entries.sort();
+
+    let mut hasher = Sha256::new();
+    for (relative_path, path) in entries {
+        let mut file = open_file(&path)?;
+        let len = file
+            .metadata()
+            .map_err(|e| FileReadError::convert(e, &path))?
+            .len();
+        hasher.update(relative_path.as_bytes());
+        hasher.update([0]);
+        hasher.update(len.to_le_bytes());
+        copy(&mut file, &mut hasher).map_err(|e| FileReadError::convert(e, &path))?;
+    }
+
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Returns the hex-encoded SHA-256 digest of the contents of the file at
+/// `path`
+#[allow(unused)]
+pub fn file_sha256(path: &Path) -> StdResult<String, FileReadError> {
+    let mut file = open_file(path)?;
+    let mut hasher = Sha256::new();
+    copy(&mut file, &mut hasher).map_err(|e| FileReadError::convert(e, path))?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{file_sha256, hash_dir_tree};
+    use anyhow::Result;
+    use std::fs::{create_dir_all, write};
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_hash_dir_tree_stable() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        create_dir_all(temp_dir.path().join("aaa"))?;
+        write(temp_dir.path().join("file0.txt"), "hello")?;
+        write(temp_dir.path().join("aaa").join("file1.txt"), "world")?;
+
+        // Act
+        let hash0 = hash_dir_tree(temp_dir.path())?;
+        let hash1 = hash_dir_tree(temp_dir.path())?;
+
+        // Assert
+        assert_eq!(64, hash0.len());
+        assert_eq!(hash0, hash1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_hash_dir_tree_creation_order_does_not_matter() -> Result<()> {
+        // Arrange
+        let temp_dir0 = TempDir::new("joatmon-test")?;
+        write(temp_dir0.path().join("aaa.txt"), "hello")?;
+        write(temp_dir0.path().join("bbb.txt"), "world")?;
+        let temp_dir1 = TempDir::new("joatmon-test")?;
+        write(temp_dir1.path().join("bbb.txt"), "world")?;
+        write(temp_dir1.path().join("aaa.txt"), "hello")?;
+
+        // Act
+        let hash0 = hash_dir_tree(temp_dir0.path())?;
+        let hash1 = hash_dir_tree(temp_dir1.path())?;
+
+        // Assert
+        assert_eq!(hash0, hash1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_hash_dir_tree_content_change() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.txt");
+        write(&path, "hello")?;
+        let hash0 = hash_dir_tree(temp_dir.path())?;
+        write(&path, "world")?;
+
+        // Act
+        let hash1 = hash_dir_tree(temp_dir.path())?;
+
+        // Assert
+        assert_ne!(hash0, hash1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_sha256_succeeds() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.txt");
+        write(&path, "hello-world")?;
+
+        // Act
+        let hash = file_sha256(&path)?;
+
+        // Assert
+        assert_eq!(
+            "afa27b44d43b02a9fea41d13cedc2e4016cfcf87c5dbf990e593669aa8ce286d",
+            hash
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,162 @@
+// Copyright (c) 2023 Richard Cook
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the
+// "Software"), to deal in the Software without restriction, including
+// without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to
+// permit persons to whom the Software is furnished to do so, subject to
+// the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE
+// LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION
+// WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+//
+use super::paths::extended_length_path;
+use super::write::FileWriteError;
+use fs2::FileExt;
+use std::fs::{File, OpenOptions};
+use std::path::Path;
+use std::result::Result as StdResult;
+
+// Releases the lock when dropped, including during unwinding
+struct LockedFile(File);
+
+impl Drop for LockedFile {
+    fn drop(&mut self) {
+        _ = FileExt::unlock(&self.0);
+    }
+}
+
+/// Opens the existing file at `path` for reading and writing, takes an
+/// exclusive lock on it and runs `f` while holding the lock
+///
+/// The lock is released when `f` returns or panics. Locks are advisory
+/// so they only exclude other processes and threads that also lock the
+/// file
+#[allow(unused)]
+pub fn with_locked_file<T, F>(path: impl AsRef<Path>, f: F) -> StdResult<T, FileWriteError>
+where
+    F: FnOnce(&mut File) -> StdResult<T, FileWriteError>,
+{
+    let path = path.as_ref();
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(extended_length_path(path))
+        .map_err(|e| FileWriteError::convert(e, path))?;
+    file.lock_exclusive()
+        .map_err(|e| FileWriteError::convert(e, path))?;
+    let mut locked_file = LockedFile(file);
+    f(&mut locked_file.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::with_locked_file;
+    use crate::fs::FileWriteError;
+    use anyhow::Result;
+    use fs2::FileExt;
+    use std::fs::{read_to_string, write, File};
+    use std::io::{Read, Seek, SeekFrom, Write};
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::path::Path;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread::{scope, sleep};
+    use std::time::Duration;
+    use tempdir::TempDir;
+
+    fn bump(file: &mut File) -> std::io::Result<()> {
+        let mut s = String::new();
+        file.read_to_string(&mut s)?;
+        let value = s.trim().parse::<u32>().unwrap_or_default();
+        // Widen the window in which an unlocked update would be lost
+        sleep(Duration::from_millis(50));
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        write!(file, "{}", value + 1)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_with_locked_file_does_not_interleave() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("version.txt");
+        write(&path, "0")?;
+        let active = AtomicUsize::new(0);
+        let max_active = AtomicUsize::new(0);
+        let locked_bump = |path: &Path| {
+            with_locked_file(path, |file| {
+                let n = active.fetch_add(1, Ordering::SeqCst) + 1;
+                max_active.fetch_max(n, Ordering::SeqCst);
+                let result = bump(file).map_err(FileWriteError::other);
+                active.fetch_sub(1, Ordering::SeqCst);
+                result
+            })
+        };
+
+        // Act
+        scope(|s| {
+            let handles = [
+                s.spawn(|| locked_bump(&path)),
+                s.spawn(|| locked_bump(&path)),
+            ];
+            for handle in handles {
+                handle
+                    .join()
+                    .expect("thread must not panic")
+                    .expect("with_locked_file must succeed");
+            }
+        });
+
+        // Assert
+        assert_eq!(1, max_active.load(Ordering::SeqCst));
+        assert_eq!("2", read_to_string(&path)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_with_locked_file_releases_on_panic() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("version.txt");
+        write(&path, "0")?;
+
+        // Act
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            with_locked_file(&path, |_| -> Result<(), FileWriteError> {
+                panic!("closure panicked")
+            })
+        }));
+
+        // Assert
+        assert!(result.is_err());
+        File::open(&path)?.try_lock_exclusive()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_with_locked_file_not_found_fails() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("version.txt");
+
+        // Act
+        let Err(e) = with_locked_file(&path, |_| Ok(())) else {
+            panic!("with_locked_file must fail")
+        };
+
+        // Assert
+        assert!(e.is_not_found());
+        assert!(!path.exists());
+        Ok(())
+    }
+}
@@ -20,15 +20,58 @@
 // WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
 //
 mod backup;
+mod copy;
+#[cfg(feature = "similar")]
+mod diff;
 mod find;
+mod hash;
+#[cfg(feature = "fs2")]
+mod lock;
 mod paths;
 mod read;
+mod stat;
+mod walk;
 mod working_dir;
 mod write;
 
-pub use self::backup::safe_back_up;
-pub use self::find::{find_sentinel_dir, find_sentinel_file};
-pub use self::paths::{file_name_safe_timestamp, label_file_name};
-pub use self::read::{open_file, read_bytes, read_text_file, FileReadError, FileReadErrorKind};
+pub use self::backup::{
+    backup_path_for, needs_backup, prune_backups_older_than, safe_back_up, safe_back_up_info,
+    safe_back_up_many, safe_back_up_with, BackupInfo, BackupNaming,
+};
+#[cfg(feature = "flate2")]
+pub use self::backup::{safe_back_up_compressed, safe_back_up_compressed_default};
+pub use self::copy::{copy_file_verified, copy_file_with_progress, copy_if_newer};
+#[cfg(feature = "similar")]
+pub use self::diff::text_file_diff;
+#[cfg(unix)]
+pub use self::find::find_sentinel_file_same_device;
+pub use self::find::{
+    find_sentinel_dir, find_sentinel_dir_with_walk, find_sentinel_file, find_sentinel_file_ci,
+    find_sentinel_file_with_depth, find_sentinel_file_with_walk, AncestorWalk,
+    DEFAULT_SENTINEL_LIMIT,
+};
+pub use self::hash::{file_sha256, hash_dir_tree};
+#[cfg(feature = "fs2")]
+pub use self::lock::with_locked_file;
+pub use self::paths::{
+    expand_tilde, file_name_safe_timestamp, is_within, label_file_name, relativize,
+};
+#[cfg(feature = "mmap")]
+pub use self::read::map_file;
+pub use self::read::{
+    count_lines, detect_line_ending, open_file, read_bytes, read_bytes_into, read_bytes_range,
+    read_bytes_retry, read_bytes_with_hash, read_link, read_nth_line, read_text_file,
+    read_text_file_lossy, read_text_file_no_bom, FileReadError, FileReadErrorKind,
+};
+#[cfg(feature = "filetime")]
+pub use self::stat::set_modified;
+pub use self::stat::{get_modified, path_kind, stat, FileInfo, PathKind};
+#[cfg(feature = "glob")]
+pub use self::walk::glob;
+pub use self::walk::{find_files_with_extension, walk_dir};
 pub use self::working_dir::WorkingDirectory;
-pub use self::write::{safe_create_file, safe_write_file, FileWriteError, FileWriteErrorKind};
+pub use self::write::{
+    append_file, ensure_parent_dir, safe_create_file, safe_remove_dir_all, safe_symlink,
+    safe_write_file, safe_write_file_like, safe_write_file_normalized, safe_write_text_file,
+    FileWriteError, FileWriteErrorKind, LineEnding, SafeWriter, TempFile, WriteOptions,
+};
@@ -19,9 +19,15 @@
 // IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
 // CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
 //
+use super::read::FileReadError;
 use chrono::{DateTime, SecondsFormat, Utc};
+use std::borrow::Cow;
+use std::env::var_os;
 use std::ffi::OsString;
-use std::path::{Path, PathBuf};
+use std::fs::{canonicalize, symlink_metadata};
+use std::io::Result as IOResult;
+use std::path::{absolute, Component, Path, PathBuf};
+use std::result::Result as StdResult;
 
 #[must_use]
 pub fn label_file_name(path: &Path, label: &str) -> Option<PathBuf> {
@@ -50,12 +56,160 @@ pub fn file_name_safe_timestamp(dt: &DateTime<Utc>) -> String {
         .replace(['-', ':', '.'], "")
 }
 
+/// Replaces a leading `~` component in `path` with the user's home
+/// directory; other paths, and all paths if the home directory cannot be
+/// determined, are returned unchanged
+#[must_use]
+pub fn expand_tilde(path: &Path) -> PathBuf {
+    let home = var_os("HOME")
+        .or_else(|| var_os("USERPROFILE"))
+        .filter(|s| !s.is_empty())
+        .map(PathBuf::from);
+    expand_tilde_with(path, home.as_deref())
+}
+
+fn expand_tilde_with(path: &Path, home: Option<&Path>) -> PathBuf {
+    match (path.strip_prefix("~"), home) {
+        (Ok(rest), Some(home)) if rest.as_os_str().is_empty() => home.to_path_buf(),
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Expresses `path` relative to `base`, using `..` components where
+/// necessary, or returns `None` if the paths have no common prefix
+///
+/// Both paths are canonicalized first if they exist
+#[must_use]
+pub fn relativize(path: &Path, base: &Path) -> Option<PathBuf> {
+    let path = canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let base = canonicalize(base).unwrap_or_else(|_| base.to_path_buf());
+    let path_components = path.components().collect::<Vec<_>>();
+    let base_components = base.components().collect::<Vec<_>>();
+
+    let common = path_components
+        .iter()
+        .zip(&base_components)
+        .take_while(|(a, b)| a == b)
+        .count();
+    if common == 0 {
+        return None;
+    }
+
+    let mut result = PathBuf::new();
+    for _ in common..base_components.len() {
+        result.push("..");
+    }
+    for component in &path_components[common..] {
+        result.push(component);
+    }
+
+    if result.as_os_str().is_empty() {
+        result.push(".");
+    }
+
+    Some(result)
+}
+
+/// Returns whether `path` resolves to `base` or a location beneath it
+///
+/// Symbolic links and `..` components are resolved in the existing part
+/// of `path` and the remainder, which does not exist, is normalized
+/// lexically. `base` must exist and a dangling symbolic link in `path`
+/// is reported as an error
+pub fn is_within(base: &Path, path: &Path) -> StdResult<bool, FileReadError> {
+    let resolved_base = canonicalize(base).map_err(|e| FileReadError::convert(e, base))?;
+    let resolved_path = resolve(path).map_err(|e| FileReadError::convert(e, path))?;
+    Ok(resolved_path.starts_with(resolved_base))
+}
+
+fn resolve(path: &Path) -> IOResult<PathBuf> {
+    let mut resolved = PathBuf::new();
+    for component in absolute(path)?.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => _ = resolved.pop(),
+            _ => {
+                resolved.push(component);
+                if symlink_metadata(&resolved).is_ok() {
+                    resolved = canonicalize(&resolved)?;
+                }
+            }
+        }
+    }
+    Ok(resolved)
+}
+
+// Converts absolute paths that may exceed the legacy MAX_PATH limit to
+// extended-length (\\?\) form so that Win32 APIs will accept them. The
+// lower limit for directories, which reserves 12 characters for an 8.3
+// file name, is applied to all paths so that creating a directory with a
+// path of 248 to 259 characters also succeeds
+// Callers must continue to use the original path in error messages
+#[cfg(windows)]
+pub fn extended_length_path(path: &Path) -> Cow<'_, Path> {
+    use std::path::Prefix;
+
+    const MAX_DIR_PATH: usize = 260 - 12;
+
+    if path.as_os_str().len() < MAX_DIR_PATH || !path.is_absolute() {
+        return Cow::Borrowed(path);
+    }
+
+    let mut components = path.components();
+    let mut s = OsString::new();
+    match components.next() {
+        Some(Component::Prefix(p)) => match p.kind() {
+            Prefix::Disk(_) => {
+                s.push(r"\\?\");
+                s.push(p.as_os_str());
+            }
+            Prefix::UNC(server, share) => {
+                s.push(r"\\?\UNC\");
+                s.push(server);
+                s.push(r"\");
+                s.push(share);
+            }
+            _ => return Cow::Borrowed(path),
+        },
+        _ => return Cow::Borrowed(path),
+    }
+
+    // Verbatim paths are not normalized by Windows so "." and ".."
+    // must be resolved here
+    let mut parts = Vec::new();
+    for component in components {
+        match component {
+            Component::Normal(part) => parts.push(part),
+            Component::ParentDir => _ = parts.pop(),
+            _ => {}
+        }
+    }
+
+    for part in parts {
+        s.push(r"\");
+        s.push(part);
+    }
+
+    Cow::Owned(PathBuf::from(s))
+}
+
+#[cfg(not(windows))]
+pub const fn extended_length_path(path: &Path) -> Cow<'_, Path> {
+    Cow::Borrowed(path)
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{file_name_safe_timestamp, label_file_name};
+    use super::{
+        expand_tilde_with, file_name_safe_timestamp, is_within, label_file_name, relativize,
+    };
+    use anyhow::Result;
     use chrono::{TimeZone, Utc};
     use rstest::rstest;
-    use std::path::PathBuf;
+    use std::fs::{create_dir_all, write};
+    use std::path::{Path, PathBuf};
+    use tempdir::TempDir;
 
     #[rstest]
     #[case(Some(PathBuf::from("/aaa/bbb/ccc-ddd.txt")), "/aaa/bbb/ccc.txt", "ddd")]
@@ -78,4 +232,120 @@ mod tests {
             .expect("must be valid");
         assert_eq!("20190317T164300000Z", file_name_safe_timestamp(&dt));
     }
+
+    #[rstest]
+    #[case("/home/user", "~")]
+    #[case("/home/user/sub", "~/sub")]
+    #[case("/home/user/sub/file.txt", "~/sub/file.txt")]
+    #[case("aaa/~/bbb", "aaa/~/bbb")]
+    #[case("~user/sub", "~user/sub")]
+    #[case("/aaa/bbb", "/aaa/bbb")]
+    fn expand_tilde_basics(#[case] expected_path: PathBuf, #[case] path: PathBuf) {
+        assert_eq!(
+            expected_path,
+            expand_tilde_with(&path, Some(Path::new("/home/user")))
+        );
+    }
+
+    #[test]
+    fn expand_tilde_no_home() {
+        assert_eq!(
+            PathBuf::from("~/sub"),
+            expand_tilde_with(Path::new("~/sub"), None)
+        );
+    }
+
+    #[test]
+    fn relativize_child() -> Result<()> {
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let dir = temp_dir.path().join("aaa").join("bbb");
+        create_dir_all(&dir)?;
+        assert_eq!(
+            Some(Path::new("aaa").join("bbb")),
+            relativize(&dir, temp_dir.path())
+        );
+        assert_eq!(Some(PathBuf::from(".")), relativize(&dir, &dir));
+        Ok(())
+    }
+
+    #[test]
+    fn relativize_sibling() -> Result<()> {
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let dir0 = temp_dir.path().join("aaa").join("bbb");
+        let dir1 = temp_dir.path().join("ccc");
+        create_dir_all(&dir0)?;
+        create_dir_all(&dir1)?;
+        assert_eq!(
+            Some(Path::new("..").join("aaa").join("bbb")),
+            relativize(&dir0, &dir1)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn relativize_trailing_slash() {
+        assert_eq!(
+            Some(PathBuf::from("ccc")),
+            relativize(Path::new("/aaa/bbb/ccc"), Path::new("/aaa/bbb/"))
+        );
+    }
+
+    #[test]
+    fn relativize_unrelated() {
+        assert_eq!(None, relativize(Path::new("aaa/bbb"), Path::new("ccc/ddd")));
+    }
+
+    #[rstest]
+    #[case(true, "aaa")]
+    #[case(true, "aaa/file.txt")]
+    #[case(true, "aaa/bbb/missing.txt")]
+    #[case(true, "aaa/missing/../bbb")]
+    #[case(true, "aaa/./bbb/..")]
+    #[case(false, "aaa/..")]
+    #[case(false, "aaa/bbb/../../ccc")]
+    #[case(false, "aaa/missing/../../ccc")]
+    #[case(false, "aaab")]
+    #[case(false, "ccc/file.txt")]
+    fn is_within_basics(#[case] expected: bool, #[case] relative_path: &str) -> Result<()> {
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let base = temp_dir.path().join("aaa");
+        create_dir_all(base.join("bbb"))?;
+        create_dir_all(temp_dir.path().join("aaab"))?;
+        create_dir_all(temp_dir.path().join("ccc"))?;
+        write(base.join("file.txt"), "hello")?;
+        assert_eq!(
+            expected,
+            is_within(&base, &temp_dir.path().join(relative_path))?
+        );
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn is_within_symlink_escape() -> Result<()> {
+        use std::os::unix::fs::symlink;
+
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let base = temp_dir.path().join("aaa");
+        let outside = temp_dir.path().join("ccc");
+        create_dir_all(&base)?;
+        create_dir_all(&outside)?;
+        symlink(&outside, base.join("link"))?;
+        symlink(&base, outside.join("back"))?;
+        assert!(!is_within(&base, &base.join("link"))?);
+        assert!(!is_within(&base, &base.join("link").join("missing.txt"))?);
+        assert!(is_within(&base, &outside.join("back").join("missing.txt"))?);
+        Ok(())
+    }
+
+    #[test]
+    fn is_within_missing_base_fails() -> Result<()> {
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let base = temp_dir.path().join("aaa");
+        let Err(e) = is_within(&base, temp_dir.path()) else {
+            panic!("is_within must fail")
+        };
+        assert!(e.is_not_found());
+        Ok(())
+    }
 }
@@ -19,26 +19,43 @@
 // OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION
 // WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
 //
+use super::paths::extended_length_path;
+use super::write::LineEnding;
 use crate::error::HasOtherError;
 use anyhow::Error as AnyhowError;
+#[cfg(feature = "mmap")]
+use memmap2::Mmap;
+use sha2::{Digest, Sha256};
+use std::any::Any;
 use std::error::Error as StdError;
 use std::fmt::{Debug, Display};
-use std::fs::{read, read_to_string, File};
-use std::io::Error as IOError;
+use std::fs::{read, symlink_metadata, File};
+use std::io::{
+    BufRead, BufReader, Error as IOError, ErrorKind as IOErrorKind, Read, Result as IOResult, Seek,
+    SeekFrom,
+};
 use std::path::{Path, PathBuf};
 use std::result::Result as StdResult;
+use std::str::Utf8Error;
+use std::thread::sleep;
+use std::time::Duration;
 use thiserror::Error;
 
+const LINE_ENDING_SCAN_LIMIT: u64 = 64 * 1024;
+
 #[allow(unused)]
 #[derive(Debug, PartialEq)]
 #[non_exhaustive]
 pub enum FileReadErrorKind {
     IsADirectory,
+    InvalidUtf8 { offset: usize },
+    Malformed { line: usize },
+    NotASymlink,
     NotFound,
     Other,
 }
 
-#[derive(Debug, Error)]
+#[derive(Debug, Error, PartialEq)]
 #[error(transparent)]
 pub struct FileReadError(#[from] FileReadErrorImpl);
 
@@ -48,17 +65,48 @@ impl FileReadError {
     pub const fn kind(&self) -> FileReadErrorKind {
         match self.0 {
             FileReadErrorImpl::IsADirectory(_) => FileReadErrorKind::IsADirectory,
+            FileReadErrorImpl::InvalidUtf8 { offset, .. } => {
+                FileReadErrorKind::InvalidUtf8 { offset }
+            }
+            FileReadErrorImpl::Malformed { line, .. } => FileReadErrorKind::Malformed { line },
+            FileReadErrorImpl::NotASymlink(_) => FileReadErrorKind::NotASymlink,
             FileReadErrorImpl::NotFound(_) => FileReadErrorKind::NotFound,
             _ => FileReadErrorKind::Other,
         }
     }
 
+    /// Returns whether the error is of the given kind
+    #[allow(unused)]
+    #[allow(clippy::needless_pass_by_value)]
+    #[must_use]
+    pub fn is_kind(&self, kind: FileReadErrorKind) -> bool {
+        self.kind() == kind
+    }
+
     #[allow(unused)]
     #[must_use]
     pub fn is_is_a_directory(&self) -> bool {
         self.kind() == FileReadErrorKind::IsADirectory
     }
 
+    #[allow(unused)]
+    #[must_use]
+    pub const fn is_invalid_utf8(&self) -> bool {
+        matches!(self.kind(), FileReadErrorKind::InvalidUtf8 { .. })
+    }
+
+    #[allow(unused)]
+    #[must_use]
+    pub const fn is_malformed(&self) -> bool {
+        matches!(self.kind(), FileReadErrorKind::Malformed { .. })
+    }
+
+    #[allow(unused)]
+    #[must_use]
+    pub fn is_not_a_symlink(&self) -> bool {
+        self.kind() == FileReadErrorKind::NotASymlink
+    }
+
     #[allow(unused)]
     #[must_use]
     pub fn is_not_found(&self) -> bool {
@@ -71,39 +119,59 @@ impl FileReadError {
         self.kind() == FileReadErrorKind::Other
     }
 
-    fn other<E>(e: E) -> Self
+    /// Returns the path associated with the error, if any
+    #[allow(unused)]
+    #[must_use]
+    pub fn path(&self) -> Option<&Path> {
+        match self.0 {
+            FileReadErrorImpl::IsADirectory(ref p)
+            | FileReadErrorImpl::InvalidUtf8 { path: ref p, .. }
+            | FileReadErrorImpl::Malformed { path: ref p, .. }
+            | FileReadErrorImpl::NotASymlink(ref p)
+            | FileReadErrorImpl::NotFound(ref p) => Some(p),
+            FileReadErrorImpl::Other(_) => None,
+        }
+    }
+
+    pub(super) fn other<E>(e: E) -> Self
     where
         E: StdError + Send + Sync + 'static,
     {
         Self(FileReadErrorImpl::Other(AnyhowError::new(e)))
     }
 
-    fn convert(e: IOError, path: &Path) -> Self {
-        use std::io::ErrorKind::{self, *};
+    pub(crate) fn malformed(path: &Path, line: usize, message: impl Into<String>) -> Self {
+        Self(FileReadErrorImpl::Malformed {
+            path: path.to_path_buf(),
+            line,
+            message: message.into(),
+        })
+    }
 
-        #[cfg(target_os = "windows")]
-        fn is_is_a_directory(kind: ErrorKind, path: &Path) -> bool {
-            kind == PermissionDenied && path.is_dir()
+    pub(crate) fn convert(e: IOError, path: &Path) -> Self {
+        if e.kind() == IOErrorKind::NotFound {
+            return Self(FileReadErrorImpl::NotFound(path.to_path_buf()));
         }
 
-        #[cfg(not(target_os = "windows"))]
-        fn is_is_a_directory(kind: ErrorKind, _path: &Path) -> bool {
-            // io_error_more adds std::io::ErrorKind::IsADirectory etc.
-            // https://doc.rust-lang.org/stable/std/io/enum.ErrorKind.html#variant.IsADirectory
-            // For now, we'll match on the debug string for these unstable values
-            format!("{kind:?}").as_str() == "IsADirectory"
+        if let Some(e) = e.get_ref().and_then(|e| e.downcast_ref::<Utf8Error>()) {
+            return Self(FileReadErrorImpl::InvalidUtf8 {
+                path: path.to_path_buf(),
+                offset: e.valid_up_to(),
+            });
         }
 
-        let kind = e.kind();
-
-        if is_is_a_directory(kind, path) {
+        // Windows reports PermissionDenied when opening a directory as a
+        // file, so consult the file system to tell this apart from a
+        // genuine permission error
+        let maybe_directory = match e.kind() {
+            IOErrorKind::IsADirectory => true,
+            IOErrorKind::PermissionDenied => cfg!(windows),
+            _ => false,
+        };
+        if maybe_directory && extended_length_path(path).is_dir() {
             return Self(FileReadErrorImpl::IsADirectory(path.to_path_buf()));
         }
 
-        if kind == NotFound {
-            return Self(FileReadErrorImpl::NotFound(path.to_path_buf()));
-        }
-
         Self::other(e)
     }
 }
@@ -113,49 +181,334 @@ impl HasOtherError for FileReadError {
         self.is_other()
     }
 
-    fn downcast_other_ref<E>(&self) -> Option<&E>
-    where
-        E: Display + Debug + Send + Sync + 'static,
-    {
+    fn downcast_other_any(&self) -> Option<&dyn Any> {
         if let FileReadErrorImpl::Other(ref inner) = self.0 {
-            inner.downcast_ref::<E>()
+            Some(inner)
         } else {
             None
         }
     }
+
+    fn downcast_other<E>(self) -> StdResult<E, Self>
+    where
+        E: Display + Debug + Send + Sync + 'static,
+    {
+        match self.0 {
+            FileReadErrorImpl::Other(inner) => inner
+                .downcast::<E>()
+                .map_err(|inner| Self(FileReadErrorImpl::Other(inner))),
+            inner => Err(Self(inner)),
+        }
+    }
 }
 
 #[derive(Debug, Error)]
 enum FileReadErrorImpl {
     #[error("File system object {0} is a directory not a file")]
     IsADirectory(PathBuf),
+    #[error("File {path} contains invalid UTF-8 at byte offset {offset}")]
+    InvalidUtf8 { path: PathBuf, offset: usize },
+    #[error("File {path} is malformed at line {line}: {message}")]
+    Malformed {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+    #[error("File system object {0} is not a symbolic link")]
+    NotASymlink(PathBuf),
     #[error("File {0} not found")]
     NotFound(PathBuf),
     #[error(transparent)]
     Other(AnyhowError),
 }
 
+// Compares classified errors by path and treats all other errors as equal
+impl PartialEq for FileReadErrorImpl {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::IsADirectory(a), Self::IsADirectory(b))
+            | (Self::NotASymlink(a), Self::NotASymlink(b))
+            | (Self::NotFound(a), Self::NotFound(b)) => a == b,
+            (
+                Self::InvalidUtf8 {
+                    path: a,
+                    offset: a_offset,
+                },
+                Self::InvalidUtf8 {
+                    path: b,
+                    offset: b_offset,
+                },
+            ) => a == b && a_offset == b_offset,
+            (
+                Self::Malformed {
+                    path: a,
+                    line: a_line,
+                    ..
+                },
+                Self::Malformed {
+                    path: b,
+                    line: b_line,
+                    ..
+                },
+            ) => a == b && a_line == b_line,
+            (Self::Other(_), Self::Other(_)) => true,
+            _ => false,
+        }
+    }
+}
+
 #[allow(unused)]
-pub fn read_text_file(path: &Path) -> StdResult<String, FileReadError> {
-    read_to_string(path).map_err(|e| FileReadError::convert(e, path))
+pub fn read_text_file(path: impl AsRef<Path>) -> StdResult<String, FileReadError> {
+    let path = path.as_ref();
+    String::from_utf8(read_bytes(path)?).map_err(|e| {
+        FileReadError::convert(IOError::new(IOErrorKind::InvalidData, e.utf8_error()), path)
+    })
 }
 
+/// Reads the file at `path` as text, removing a leading UTF-8 byte
+/// order mark if present
 #[allow(unused)]
-pub fn open_file(path: &Path) -> StdResult<File, FileReadError> {
-    File::open(path).map_err(|e| FileReadError::convert(e, path))
+pub fn read_text_file_no_bom(path: impl AsRef<Path>) -> StdResult<String, FileReadError> {
+    let path = path.as_ref();
+    let mut s = read_text_file(path)?;
+    if s.starts_with('\u{feff}') {
+        s.drain(..'\u{feff}'.len_utf8());
+    }
+    Ok(s)
 }
 
+/// Reads the file at `path` as text, replacing invalid UTF-8 sequences
+/// with U+FFFD
 #[allow(unused)]
-pub fn read_bytes(path: &Path) -> StdResult<Vec<u8>, FileReadError> {
-    read(path).map_err(|e| FileReadError::convert(e, path))
+pub fn read_text_file_lossy(path: impl AsRef<Path>) -> StdResult<String, FileReadError> {
+    let path = path.as_ref();
+    let bytes = read_bytes(path)?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+#[allow(unused)]
+pub fn open_file(path: impl AsRef<Path>) -> StdResult<File, FileReadError> {
+    let path = path.as_ref();
+    File::open(extended_length_path(path)).map_err(|e| FileReadError::convert(e, path))
+}
+
+#[allow(unused)]
+pub fn read_bytes(path: impl AsRef<Path>) -> StdResult<Vec<u8>, FileReadError> {
+    let path = path.as_ref();
+    read(extended_length_path(path)).map_err(|e| FileReadError::convert(e, path))
+}
+
+#[allow(unused)]
+pub fn read_bytes_into(
+    path: impl AsRef<Path>,
+    buf: &mut Vec<u8>,
+) -> StdResult<usize, FileReadError> {
+    let path = path.as_ref();
+    buf.clear();
+    let mut file = open_file(path)?;
+    if let Ok(metadata) = file.metadata() {
+        buf.reserve(usize::try_from(metadata.len()).unwrap_or_default());
+    }
+    file.read_to_end(buf)
+        .map_err(|e| FileReadError::convert(e, path))
+}
+
+/// Reads the contents of the file at `path` and returns them together
+/// with their hex-encoded SHA-256 digest, computed in the same pass
+#[allow(unused)]
+pub fn read_bytes_with_hash(path: impl AsRef<Path>) -> StdResult<(Vec<u8>, String), FileReadError> {
+    let path = path.as_ref();
+    let mut file = open_file(path)?;
+    let mut bytes = Vec::new();
+    if let Ok(metadata) = file.metadata() {
+        bytes.reserve(usize::try_from(metadata.len()).unwrap_or_default());
+    }
+    let mut hasher = Sha256::new();
+    let mut chunk = [0; 8192];
+    loop {
+        let n = match file.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == IOErrorKind::Interrupted => continue,
+            Err(e) => return Err(FileReadError::convert(e, path)),
+        };
+        hasher.update(&chunk[..n]);
+        bytes.extend_from_slice(&chunk[..n]);
+    }
+    Ok((bytes, hex::encode(hasher.finalize())))
+}
+
+/// Reads up to `len` bytes from the file at `path` starting at byte
+/// offset `start`, returning fewer bytes if the end of the file is
+/// reached first
+#[allow(unused)]
+pub fn read_bytes_range(
+    path: impl AsRef<Path>,
+    start: u64,
+    len: usize,
+) -> StdResult<Vec<u8>, FileReadError> {
+    let path = path.as_ref();
+    let mut file = open_file(path)?;
+    file.seek(SeekFrom::Start(start))
+        .map_err(|e| FileReadError::convert(e, path))?;
+    let mut bytes = Vec::new();
+    if let Ok(metadata) = file.metadata() {
+        let available = usize::try_from(metadata.len().saturating_sub(start)).unwrap_or_default();
+        bytes.reserve(available.min(len));
+    }
+    file.take(u64::try_from(len).unwrap_or(u64::MAX))
+        .read_to_end(&mut bytes)
+        .map_err(|e| FileReadError::convert(e, path))?;
+    Ok(bytes)
+}
+
+/// Reads the file at `path`, making up to `attempts` attempts and
+/// sleeping for `delay` between attempts that fail with a transient error
+#[allow(unused)]
+pub fn read_bytes_retry(
+    path: impl AsRef<Path>,
+    attempts: usize,
+    delay: Duration,
+) -> StdResult<Vec<u8>, FileReadError> {
+    let path = path.as_ref();
+    read_with_retry(path, attempts, delay, || read(extended_length_path(path)))
+}
+
+fn read_with_retry<F>(
+    path: &Path,
+    attempts: usize,
+    delay: Duration,
+    mut f: F,
+) -> StdResult<Vec<u8>, FileReadError>
+where
+    F: FnMut() -> IOResult<Vec<u8>>,
+{
+    let mut remaining = attempts.max(1);
+    loop {
+        remaining -= 1;
+        match f() {
+            Ok(bytes) => return Ok(bytes),
+            Err(e)
+                if remaining > 0
+                    && matches!(
+                        e.kind(),
+                        IOErrorKind::Interrupted | IOErrorKind::WouldBlock | IOErrorKind::TimedOut
+                    ) =>
+            {
+                sleep(delay);
+            }
+            Err(e) => return Err(FileReadError::convert(e, path)),
+        }
+    }
+}
+
+/// Returns the style of the first line ending in the file at `path` or
+/// `None` if there is no newline within the first 64 KiB of the file
+#[allow(unused)]
+pub fn detect_line_ending(path: impl AsRef<Path>) -> StdResult<Option<LineEnding>, FileReadError> {
+    let path = path.as_ref();
+    let mut reader = BufReader::new(open_file(path)?.take(LINE_ENDING_SCAN_LIMIT));
+    let mut line = Vec::new();
+    reader
+        .read_until(b'\n', &mut line)
+        .map_err(|e| FileReadError::convert(e, path))?;
+    Ok(if line.ends_with(b"\r\n") {
+        Some(LineEnding::CrLf)
+    } else if line.ends_with(b"\n") {
+        Some(LineEnding::Lf)
+    } else {
+        None
+    })
+}
+
+/// Returns the 1-based line `n` of the file at `path`, without its line
+/// ending, or `None` if the file has fewer than `n` lines
+#[allow(unused)]
+pub fn read_nth_line(path: impl AsRef<Path>, n: usize) -> StdResult<Option<String>, FileReadError> {
+    let path = path.as_ref();
+    if n == 0 {
+        return Ok(None);
+    }
+
+    BufReader::new(open_file(path)?)
+        .lines()
+        .nth(n - 1)
+        .transpose()
+        .map_err(|e| FileReadError::convert(e, path))
+}
+
+/// Counts the lines in the file at `path` without reading it into
+/// memory; a final line without a trailing newline is counted
+#[allow(unused)]
+#[allow(clippy::naive_bytecount)]
+pub fn count_lines(path: impl AsRef<Path>) -> StdResult<usize, FileReadError> {
+    let path = path.as_ref();
+    let mut file = open_file(path)?;
+    let mut buffer = vec![0; 64 * 1024];
+    let mut count = 0;
+    let mut last = None;
+    loop {
+        let n = match file.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == IOErrorKind::Interrupted => continue,
+            Err(e) => return Err(FileReadError::convert(e, path)),
+        };
+        count += buffer[..n].iter().filter(|&&b| b == b'\n').count();
+        last = Some(buffer[n - 1]);
+    }
+
+    if matches!(last, Some(b) if b != b'\n') {
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+/// Returns the target of the symbolic link at `path` without following
+/// it any further
+#[allow(unused)]
+pub fn read_link(path: impl AsRef<Path>) -> StdResult<PathBuf, FileReadError> {
+    let path = path.as_ref();
+    let p = extended_length_path(path);
+    std::fs::read_link(&p).map_err(|e| {
+        if symlink_metadata(&p).is_ok_and(|m| !m.file_type().is_symlink()) {
+            FileReadError(FileReadErrorImpl::NotASymlink(path.to_path_buf()))
+        } else {
+            FileReadError::convert(e, path)
+        }
+    })
+}
+
+/// Memory-maps the file at `path` for read-only access
+///
+/// The mapping remains valid only while the underlying file is not
+/// modified: if another process truncates the file while it is mapped,
+/// accessing the affected pages is undefined behaviour and typically
+/// terminates the process with `SIGBUS` on Unix
+#[cfg(feature = "mmap")]
+#[allow(unused)]
+pub fn map_file(path: impl AsRef<Path>) -> StdResult<Mmap, FileReadError> {
+    let path = path.as_ref();
+    let file = open_file(path)?;
+    unsafe { Mmap::map(&file) }.map_err(|e| FileReadError::convert(e, path))
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{open_file, read_bytes, read_text_file, FileReadErrorKind};
+    use super::{
+        count_lines, detect_line_ending, open_file, read_bytes, read_bytes_into, read_bytes_range,
+        read_bytes_retry, read_bytes_with_hash, read_link, read_nth_line, read_text_file,
+        read_text_file_lossy, read_text_file_no_bom, read_with_retry, FileReadError,
+        FileReadErrorKind, LINE_ENDING_SCAN_LIMIT,
+    };
+    use crate::fs::{file_sha256, LineEnding};
     use anyhow::Result;
+    use rstest::rstest;
     use std::fs::write;
-    use std::io::Read;
+    use std::io::{Error as IOError, ErrorKind as IOErrorKind, Read};
+    use std::path::Path;
+    use std::time::Duration;
     use tempdir::TempDir;
 
     #[test]
@@ -194,6 +547,79 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_is_kind() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.txt");
+        write(&path, b"aaa\xffbbb")?;
+
+        // Act
+        let Err(not_found_error) = read_text_file(temp_dir.path().join("missing.txt")) else {
+            panic!("read_text_file must fail")
+        };
+        let Err(directory_error) = read_text_file(temp_dir.path()) else {
+            panic!("read_text_file must fail")
+        };
+        let Err(utf8_error) = read_text_file(&path) else {
+            panic!("read_text_file must fail")
+        };
+
+        // Assert
+        assert!(not_found_error.is_kind(FileReadErrorKind::NotFound));
+        assert!(!not_found_error.is_kind(FileReadErrorKind::IsADirectory));
+        assert!(directory_error.is_kind(FileReadErrorKind::IsADirectory));
+        assert!(!directory_error.is_kind(FileReadErrorKind::NotFound));
+        assert!(utf8_error.is_kind(FileReadErrorKind::InvalidUtf8 { offset: 3 }));
+        assert!(!utf8_error.is_kind(FileReadErrorKind::InvalidUtf8 { offset: 0 }));
+        assert!(!utf8_error.is_kind(FileReadErrorKind::Malformed { line: 1 }));
+        Ok(())
+    }
+
+    #[rstest]
+    #[case(FileReadErrorKind::IsADirectory, IOErrorKind::IsADirectory)]
+    #[case(FileReadErrorKind::Other, IOErrorKind::Other)]
+    #[cfg_attr(
+        not(windows),
+        case(FileReadErrorKind::Other, IOErrorKind::PermissionDenied)
+    )]
+    #[cfg_attr(
+        windows,
+        case(FileReadErrorKind::IsADirectory, IOErrorKind::PermissionDenied)
+    )]
+    fn test_convert_error_on_directory(
+        #[case] expected_kind: FileReadErrorKind,
+        #[case] io_error_kind: IOErrorKind,
+    ) -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let e = IOError::from(io_error_kind);
+
+        // Act
+        let e = FileReadError::convert(e, temp_dir.path());
+
+        // Assert
+        assert_eq!(expected_kind, e.kind());
+        Ok(())
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    #[test]
+    fn test_convert_generic_error_on_file_is_other() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.txt");
+        write(&path, "hello-world")?;
+        let e = IOError::other("generic error");
+
+        // Act
+        let e = FileReadError::convert(e, &path);
+
+        // Assert
+        assert_eq!(FileReadErrorKind::Other, e.kind());
+        Ok(())
+    }
+
     #[test]
     fn test_read_text_file_not_found_fails() -> Result<()> {
         // Arrange
@@ -216,6 +642,57 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_read_text_file_no_bom_succeeds() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let bom_path = temp_dir.path().join("bom.txt");
+        let no_bom_path = temp_dir.path().join("no-bom.txt");
+        write(&bom_path, b"\xef\xbb\xbfhello-world")?;
+        write(&no_bom_path, "hello-world")?;
+
+        // Act
+        let bom_value = read_text_file_no_bom(&bom_path)?;
+        let no_bom_value = read_text_file_no_bom(&no_bom_path)?;
+
+        // Assert
+        assert_eq!("hello-world", bom_value);
+        assert_eq!("hello-world", no_bom_value);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_text_file_lossy_succeeds() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.txt");
+        write(&path, b"hello\xffworld")?;
+
+        // Act
+        let value = read_text_file_lossy(&path)?;
+
+        // Assert
+        assert_eq!("hello\u{fffd}world", value);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_text_file_lossy_not_found_fails() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.txt");
+
+        // Act
+        let Err(e) = read_text_file_lossy(&path) else {
+            panic!("read_text_file_lossy must fail")
+        };
+
+        // Assert
+        assert_eq!(FileReadErrorKind::NotFound, e.kind());
+        assert!(e.is_not_found());
+        Ok(())
+    }
+
     #[test]
     fn test_open_file_succeeds() -> Result<()> {
         // Arrange
@@ -289,4 +766,516 @@ mod tests {
         assert!(message.contains(path.to_str().expect("must be valid string")));
         Ok(())
     }
+
+    #[test]
+    fn test_read_bytes_into_reuses_buffer() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let long_path = temp_dir.path().join("long.txt");
+        let short_path = temp_dir.path().join("short.txt");
+        write(&long_path, "hello-world")?;
+        write(&short_path, "abc")?;
+        let mut buffer = Vec::new();
+
+        // Act
+        let long_count = read_bytes_into(&long_path, &mut buffer)?;
+        let long_value = buffer.clone();
+        let short_count = read_bytes_into(&short_path, &mut buffer)?;
+
+        // Assert
+        assert_eq!(11, long_count);
+        assert_eq!(br"hello-world".to_vec(), long_value);
+        assert_eq!(3, short_count);
+        assert_eq!(br"abc".to_vec(), buffer);
+        Ok(())
+    }
+
+    #[rstest]
+    #[case(b"efgh".to_vec(), 4, 4)]
+    #[case(b"ij".to_vec(), 8, 4)]
+    #[case(Vec::new(), 10, 4)]
+    #[case(Vec::new(), 100, 4)]
+    #[case(Vec::new(), 4, 0)]
+    fn test_read_bytes_range(
+        #[case] expected: Vec<u8>,
+        #[case] start: u64,
+        #[case] len: usize,
+    ) -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.bin");
+        write(&path, "abcdefghij")?;
+
+        // Act
+        let bytes = read_bytes_range(&path, start, len)?;
+
+        // Assert
+        assert_eq!(expected, bytes);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_bytes_range_not_found_fails() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.bin");
+
+        // Act
+        let Err(e) = read_bytes_range(&path, 0, 4) else {
+            panic!("read_bytes_range must fail")
+        };
+
+        // Assert
+        assert!(e.is_not_found());
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_bytes_with_hash_succeeds() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.bin");
+        let content = (0..=250u8).cycle().take(20_000).collect::<Vec<_>>();
+        write(&path, &content)?;
+
+        // Act
+        let (bytes, hash) = read_bytes_with_hash(&path)?;
+
+        // Assert
+        assert_eq!(content, bytes);
+        assert_eq!(file_sha256(&path)?, hash);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_bytes_into_not_found_fails() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.txt");
+        let mut buffer = Vec::new();
+
+        // Act
+        let Err(e) = read_bytes_into(&path, &mut buffer) else {
+            panic!("read_bytes_into must fail")
+        };
+
+        // Assert
+        assert_eq!(FileReadErrorKind::NotFound, e.kind());
+        assert!(e.is_not_found());
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_bytes_into_is_a_directory_fails() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let mut buffer = Vec::new();
+
+        // Act
+        let Err(e) = read_bytes_into(temp_dir.path(), &mut buffer) else {
+            panic!("read_bytes_into must fail")
+        };
+
+        // Assert
+        assert_eq!(FileReadErrorKind::IsADirectory, e.kind());
+        assert!(e.is_is_a_directory());
+        Ok(())
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_map_file_succeeds() -> Result<()> {
+        use super::map_file;
+
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.txt");
+        write(&path, "hello-world")?;
+
+        // Act
+        let mmap = map_file(&path)?;
+
+        // Assert
+        assert_eq!(11, mmap.len());
+        assert_eq!(b"world", &mmap[6..]);
+        Ok(())
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_map_file_not_found_fails() -> Result<()> {
+        use super::map_file;
+
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.txt");
+
+        // Act
+        let Err(e) = map_file(&path) else {
+            panic!("map_file must fail")
+        };
+
+        // Assert
+        assert_eq!(FileReadErrorKind::NotFound, e.kind());
+        assert!(e.is_not_found());
+        Ok(())
+    }
+
+    #[test]
+    fn test_count_lines_trailing_newline_succeeds() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.txt");
+        write(&path, "aaa\nbbb\nccc\n")?;
+
+        // Act
+        let count = count_lines(&path)?;
+
+        // Assert
+        assert_eq!(3, count);
+        Ok(())
+    }
+
+    #[test]
+    fn test_count_lines_no_trailing_newline_succeeds() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.txt");
+        write(&path, "aaa\nbbb\nccc")?;
+
+        // Act
+        let count = count_lines(&path)?;
+
+        // Assert
+        assert_eq!(3, count);
+        Ok(())
+    }
+
+    #[test]
+    fn test_count_lines_empty_succeeds() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.txt");
+        write(&path, "")?;
+
+        // Act
+        let count = count_lines(&path)?;
+
+        // Assert
+        assert_eq!(0, count);
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_read_error_eq() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.txt");
+        let other_path = temp_dir.path().join("other.txt");
+
+        // Act
+        let Err(e0) = read_bytes(&path) else {
+            panic!("read_bytes must fail")
+        };
+        let Err(e1) = read_text_file(&path) else {
+            panic!("read_text_file must fail")
+        };
+        let Err(e2) = read_bytes(&other_path) else {
+            panic!("read_bytes must fail")
+        };
+        let Err(e3) = read_bytes(temp_dir.path()) else {
+            panic!("read_bytes must fail")
+        };
+
+        // Assert
+        assert_eq!(e0, e1);
+        assert_ne!(e0, e2);
+        assert_ne!(e0, e3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_bytes_retry_succeeds() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.txt");
+        write(&path, "hello-world")?;
+
+        // Act
+        let value = read_bytes_retry(&path, 3, Duration::ZERO)?;
+
+        // Assert
+        assert_eq!(br"hello-world".to_vec(), value);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_with_retry_transient_succeeds() -> Result<()> {
+        // Arrange
+        let mut count = 0;
+
+        // Act
+        let value = read_with_retry(Path::new("file.txt"), 3, Duration::ZERO, || {
+            count += 1;
+            if count < 3 {
+                Err(IOError::from(IOErrorKind::Interrupted))
+            } else {
+                Ok(b"hello-world".to_vec())
+            }
+        })?;
+
+        // Assert
+        assert_eq!(3, count);
+        assert_eq!(br"hello-world".to_vec(), value);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_with_retry_exhausted_fails() {
+        // Arrange
+        let mut count = 0;
+
+        // Act
+        let Err(e) = read_with_retry(Path::new("file.txt"), 2, Duration::ZERO, || {
+            count += 1;
+            Err(IOError::from(IOErrorKind::TimedOut))
+        }) else {
+            panic!("read_with_retry must fail")
+        };
+
+        // Assert
+        assert_eq!(2, count);
+        assert!(e.is_other());
+    }
+
+    #[test]
+    fn test_read_with_retry_not_found_fails() {
+        // Arrange
+        let mut count = 0;
+
+        // Act
+        let Err(e) = read_with_retry(Path::new("file.txt"), 3, Duration::ZERO, || {
+            count += 1;
+            Err(IOError::from(IOErrorKind::NotFound))
+        }) else {
+            panic!("read_with_retry must fail")
+        };
+
+        // Assert
+        assert_eq!(1, count);
+        assert!(e.is_not_found());
+    }
+
+    #[test]
+    fn test_detect_line_ending_crlf() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.txt");
+        write(&path, "aaa\r\nbbb\n")?;
+
+        // Act
+        let value = detect_line_ending(&path)?;
+
+        // Assert
+        assert_eq!(Some(LineEnding::CrLf), value);
+        Ok(())
+    }
+
+    #[test]
+    fn test_detect_line_ending_lf() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.txt");
+        write(&path, "aaa\nbbb\r\n")?;
+
+        // Act
+        let value = detect_line_ending(&path)?;
+
+        // Assert
+        assert_eq!(Some(LineEnding::Lf), value);
+        Ok(())
+    }
+
+    #[test]
+    fn test_detect_line_ending_no_newline() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.txt");
+        write(&path, "aaa")?;
+
+        // Act
+        let value = detect_line_ending(&path)?;
+
+        // Assert
+        assert_eq!(None, value);
+        Ok(())
+    }
+
+    #[test]
+    fn test_detect_line_ending_beyond_limit() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.txt");
+        let limit = usize::try_from(LINE_ENDING_SCAN_LIMIT)?;
+        write(&path, format!("{}\r\n", "a".repeat(limit)))?;
+
+        // Act
+        let value = detect_line_ending(&path)?;
+
+        // Assert
+        assert_eq!(None, value);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_nth_line_in_range() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.txt");
+        write(&path, "aaa\r\nbbb\nccc")?;
+
+        // Act & Assert
+        assert_eq!(Some(String::from("aaa")), read_nth_line(&path, 1)?);
+        assert_eq!(Some(String::from("bbb")), read_nth_line(&path, 2)?);
+        assert_eq!(Some(String::from("ccc")), read_nth_line(&path, 3)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_nth_line_out_of_range() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.txt");
+        write(&path, "aaa\nbbb\n")?;
+
+        // Act & Assert
+        assert_eq!(None, read_nth_line(&path, 0)?);
+        assert_eq!(None, read_nth_line(&path, 3)?);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_read_link_succeeds() -> Result<()> {
+        use std::os::unix::fs::symlink;
+
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let target = temp_dir.path().join("target.txt");
+        let link = temp_dir.path().join("link.txt");
+        symlink(&target, &link)?;
+
+        // Act
+        let result = read_link(&link)?;
+
+        // Assert
+        assert_eq!(target, result);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_link_not_a_symlink_fails() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.txt");
+        write(&path, "hello-world")?;
+
+        // Act
+        let Err(e) = read_link(&path) else {
+            panic!("read_link must fail")
+        };
+
+        // Assert
+        assert_eq!(FileReadErrorKind::NotASymlink, e.kind());
+        assert!(e.is_not_a_symlink());
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_read_link_directory_fails() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+
+        // Act
+        let Err(e) = read_link(temp_dir.path()) else {
+            panic!("read_link must fail")
+        };
+
+        // Assert
+        assert_eq!(FileReadErrorKind::NotASymlink, e.kind());
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_link_not_found_fails() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("link.txt");
+
+        // Act
+        let Err(e) = read_link(&path) else {
+            panic!("read_link must fail")
+        };
+
+        // Assert
+        assert_eq!(FileReadErrorKind::NotFound, e.kind());
+        Ok(())
+    }
+
+    #[test]
+    fn test_path_not_found() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.txt");
+
+        // Act
+        let Err(e) = read_text_file(&path) else {
+            panic!("read_text_file must fail")
+        };
+
+        // Assert
+        assert_eq!(Some(path.as_path()), e.path());
+        assert_eq!(None, FileReadError::other(IOError::other("other")).path());
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_text_file_invalid_utf8_fails() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.txt");
+        write(&path, b"hello\xffworld")?;
+
+        // Act
+        let Err(e) = read_text_file(&path) else {
+            panic!("read_text_file must fail")
+        };
+
+        // Assert
+        assert_eq!(FileReadErrorKind::InvalidUtf8 { offset: 5 }, e.kind());
+        assert!(e.is_invalid_utf8());
+        assert_eq!(Some(path.as_path()), e.path());
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_text_file_as_ref_path() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.txt");
+        write(&path, "hello-world")?;
+        let s = path.to_str().expect("must be valid string");
+
+        // Act
+        let from_str = read_text_file(s)?;
+        let from_string = read_text_file(String::from(s))?;
+        let from_path_buf = read_text_file(path.clone())?;
+
+        // Assert
+        assert_eq!("hello-world", from_str);
+        assert_eq!("hello-world", from_string);
+        assert_eq!("hello-world", from_path_buf);
+        Ok(())
+    }
 }
@@ -0,0 +1,255 @@
+// Copyright (c) 2023 Richard Cook
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the
+// "Software"), to deal in the Software without restriction, including
+// without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to
+// permit persons to whom the Software is furnished to do so, subject to
+// the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE
+// LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION
+// WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+//
+use super::paths::extended_length_path;
+use super::read::FileReadError;
+#[cfg(feature = "filetime")]
+use super::write::FileWriteError;
+#[cfg(feature = "filetime")]
+use filetime::{set_file_mtime, FileTime};
+use std::fs::{metadata, symlink_metadata, Metadata};
+use std::io::ErrorKind as IOErrorKind;
+use std::path::{Path, PathBuf};
+use std::result::Result as StdResult;
+use std::time::SystemTime;
+
+/// Kind of file system object at a path
+#[allow(unused)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PathKind {
+    File,
+    Dir,
+    Symlink,
+}
+
+/// Metadata of a file system object together with its path
+#[derive(Debug)]
+pub struct FileInfo {
+    path: PathBuf,
+    metadata: Metadata,
+}
+
+#[allow(unused)]
+impl FileInfo {
+    pub fn new(path: &Path) -> StdResult<Self, FileReadError> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            metadata: stat(path)?,
+        })
+    }
+
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    #[must_use]
+    pub const fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    #[must_use]
+    pub fn is_file(&self) -> bool {
+        self.metadata.is_file()
+    }
+
+    #[must_use]
+    pub fn is_dir(&self) -> bool {
+        self.metadata.is_dir()
+    }
+
+    #[must_use]
+    pub fn len(&self) -> u64 {
+        self.metadata.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn modified(&self) -> StdResult<SystemTime, FileReadError> {
+        self.metadata
+            .modified()
+            .map_err(|e| FileReadError::convert(e, &self.path))
+    }
+}
+
+/// Returns the metadata of `path`, following symbolic links
+#[allow(unused)]
+pub fn stat(path: &Path) -> StdResult<Metadata, FileReadError> {
+    metadata(extended_length_path(path)).map_err(|e| FileReadError::convert(e, path))
+}
+
+/// Returns the kind of object at `path`, or `None` if there is none,
+/// without following symbolic links
+#[allow(unused)]
+pub fn path_kind(path: &Path) -> StdResult<Option<PathKind>, FileReadError> {
+    let metadata = match symlink_metadata(extended_length_path(path)) {
+        Ok(m) => m,
+        Err(e) if e.kind() == IOErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(FileReadError::convert(e, path)),
+    };
+
+    let file_type = metadata.file_type();
+    Ok(Some(if file_type.is_symlink() {
+        PathKind::Symlink
+    } else if file_type.is_dir() {
+        PathKind::Dir
+    } else {
+        PathKind::File
+    }))
+}
+
+/// Returns the modification time of `path`
+#[allow(unused)]
+pub fn get_modified(path: &Path) -> StdResult<SystemTime, FileReadError> {
+    FileInfo::new(path)?.modified()
+}
+
+/// Sets the modification time of `path` to `time`
+#[cfg(feature = "filetime")]
+#[allow(unused)]
+pub fn set_modified(path: &Path, time: SystemTime) -> StdResult<(), FileWriteError> {
+    set_file_mtime(extended_length_path(path), FileTime::from_system_time(time))
+        .map_err(|e| FileWriteError::convert(e, path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{path_kind, stat, FileInfo, PathKind};
+    use crate::fs::FileReadErrorKind;
+    use anyhow::Result;
+    use std::fs::write;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_stat_file() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.txt");
+        write(&path, "hello-world")?;
+
+        // Act
+        let info = FileInfo::new(&path)?;
+
+        // Assert
+        assert!(stat(&path)?.is_file());
+        assert!(info.is_file());
+        assert!(!info.is_dir());
+        assert_eq!(11, info.len());
+        assert!(info.modified().is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_stat_dir() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+
+        // Act
+        let info = FileInfo::new(temp_dir.path())?;
+
+        // Assert
+        assert!(stat(temp_dir.path())?.is_dir());
+        assert!(!info.is_file());
+        assert!(info.is_dir());
+        Ok(())
+    }
+
+    #[test]
+    fn test_stat_missing_fails() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.txt");
+
+        // Act
+        let Err(e) = stat(&path) else {
+            panic!("stat must fail")
+        };
+
+        // Assert
+        assert_eq!(FileReadErrorKind::NotFound, e.kind());
+        assert!(FileInfo::new(&path).is_err());
+        Ok(())
+    }
+
+    #[cfg(feature = "filetime")]
+    #[test]
+    fn test_set_modified_get_modified() -> Result<()> {
+        use super::{get_modified, set_modified};
+        use std::time::{Duration, UNIX_EPOCH};
+
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.txt");
+        write(&path, "hello-world")?;
+        let time = UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+
+        // Act
+        set_modified(&path, time)?;
+
+        // Assert
+        let modified = get_modified(&path)?;
+        let delta = modified
+            .duration_since(time)
+            .unwrap_or_else(|e| e.duration());
+        assert!(delta < Duration::from_secs(2));
+        Ok(())
+    }
+
+    #[test]
+    fn test_path_kind() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.txt");
+        write(&path, "hello-world")?;
+
+        // Act
+        let file_kind = path_kind(&path)?;
+        let dir_kind = path_kind(temp_dir.path())?;
+        let missing_kind = path_kind(&temp_dir.path().join("missing.txt"))?;
+
+        // Assert
+        assert_eq!(Some(PathKind::File), file_kind);
+        assert_eq!(Some(PathKind::Dir), dir_kind);
+        assert_eq!(None, missing_kind);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_path_kind_symlink() -> Result<()> {
+        use std::os::unix::fs::symlink;
+
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let link = temp_dir.path().join("link");
+        symlink(temp_dir.path(), &link)?;
+
+        // Act
+        let kind = path_kind(&link)?;
+
+        // Assert
+        assert_eq!(Some(PathKind::Symlink), kind);
+        Ok(())
+    }
+}
@@ -0,0 +1,294 @@
+// Copyright (c) 2023 Richard Cook
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the
+// "Software"), to deal in the Software without restriction, including
+// without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to
+// permit persons to whom the Software is furnished to do so, subject to
+// the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE
+// LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION
+// WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+//
+use super::read::FileReadError;
+use std::fs::{read_dir, ReadDir};
+use std::path::{Path, PathBuf};
+use std::result::Result as StdResult;
+
+/// Lazily walks the directory tree under `root` depth-first
+///
+/// Yields the path of every file, directory and symlink below `root`.
+/// Symlinks are not followed. Errors reading individual entries are
+/// yielded without ending the walk
+#[allow(unused)]
+pub fn walk_dir(
+    root: &Path,
+) -> StdResult<impl Iterator<Item = StdResult<PathBuf, FileReadError>>, FileReadError> {
+    let entries = read_dir(root).map_err(|e| FileReadError::convert(e, root))?;
+    Ok(WalkDir {
+        stack: vec![(root.to_path_buf(), entries)],
+        pending_dir: None,
+    })
+}
+
+/// Returns the sorted paths of all files under `root` whose extension
+/// matches `ext` case-insensitively
+#[allow(unused)]
+pub fn find_files_with_extension(root: &Path, ext: &str) -> StdResult<Vec<PathBuf>, FileReadError> {
+    let ext = ext.strip_prefix('.').unwrap_or(ext);
+    let mut paths = Vec::new();
+    for path in walk_dir(root)? {
+        let path = path?;
+        let is_match = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| e.eq_ignore_ascii_case(ext));
+        if is_match && path.is_file() {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Returns the sorted paths matching `pattern`, such as `src/**/*.rs`,
+/// relative to `base`
+#[cfg(feature = "glob")]
+#[allow(unused)]
+pub fn glob(base: &Path, pattern: &str) -> StdResult<Vec<PathBuf>, FileReadError> {
+    use ::glob::{glob_with, MatchOptions, Pattern};
+
+    let full_pattern = Path::new(&Pattern::escape(&base.to_string_lossy())).join(pattern);
+    let mut paths = glob_with(&full_pattern.to_string_lossy(), MatchOptions::new())
+        .map_err(FileReadError::other)?
+        .map(|entry| {
+            entry.map_err(|e| {
+                let path = e.path().to_path_buf();
+                FileReadError::convert(e.into(), &path)
+            })
+        })
+        .collect::<StdResult<Vec<_>, _>>()?;
+    paths.sort();
+    Ok(paths)
+}
+
+struct WalkDir {
+    stack: Vec<(PathBuf, ReadDir)>,
+    pending_dir: Option<PathBuf>,
+}
+
+impl Iterator for WalkDir {
+    type Item = StdResult<PathBuf, FileReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(dir) = self.pending_dir.take() {
+            match read_dir(&dir) {
+                Ok(entries) => self.stack.push((dir, entries)),
+                Err(e) => return Some(Err(FileReadError::convert(e, &dir))),
+            }
+        }
+
+        loop {
+            let (dir, entries) = self.stack.last_mut()?;
+            match entries.next() {
+                Some(Ok(entry)) => {
+                    let path = entry.path();
+                    match entry.file_type() {
+                        Ok(file_type) => {
+                            if file_type.is_dir() {
+                                self.pending_dir = Some(path.clone());
+                            }
+                            return Some(Ok(path));
+                        }
+                        Err(e) => return Some(Err(FileReadError::convert(e, &path))),
+                    }
+                }
+                Some(Err(e)) => return Some(Err(FileReadError::convert(e, dir))),
+                None => _ = self.stack.pop(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_files_with_extension, walk_dir};
+    use crate::fs::FileReadErrorKind;
+    use anyhow::Result;
+    use std::collections::BTreeSet;
+    use std::fs::{create_dir_all, write};
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_walk_dir_succeeds() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let dir = temp_dir.path().join("aaa").join("bbb");
+        create_dir_all(&dir)?;
+        create_dir_all(temp_dir.path().join("ccc"))?;
+        let file_paths = [
+            temp_dir.path().join("file0.txt"),
+            temp_dir.path().join("aaa").join("file1.txt"),
+            dir.join("file2.txt"),
+            dir.join("file3.txt"),
+        ];
+        for path in &file_paths {
+            write(path, "hello-world")?;
+        }
+
+        // Act
+        let paths = walk_dir(temp_dir.path())?.collect::<Result<BTreeSet<_>, _>>()?;
+
+        // Assert
+        let files = paths
+            .iter()
+            .filter(|p| p.is_file())
+            .cloned()
+            .collect::<BTreeSet<_>>();
+        assert_eq!(file_paths.into_iter().collect::<BTreeSet<_>>(), files);
+        assert_eq!(7, paths.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_files_with_extension_succeeds() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let dir = temp_dir.path().join("aaa");
+        create_dir_all(dir.join("bbb.toml"))?;
+        write(temp_dir.path().join("file0.toml"), "")?;
+        write(temp_dir.path().join("file1.yaml"), "")?;
+        write(dir.join("file2.TOML"), "")?;
+        write(dir.join("file3.toml.bak"), "")?;
+        write(dir.join("bbb.toml").join("file4.toml"), "")?;
+
+        // Act
+        let paths = find_files_with_extension(temp_dir.path(), "toml")?;
+
+        // Assert
+        assert_eq!(
+            vec![
+                dir.join("bbb.toml").join("file4.toml"),
+                dir.join("file2.TOML"),
+                temp_dir.path().join("file0.toml"),
+            ],
+            paths
+        );
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_walk_dir_does_not_follow_symlinks() -> Result<()> {
+        use std::os::unix::fs::symlink;
+
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let target_dir = TempDir::new("joatmon-test")?;
+        write(target_dir.path().join("file.txt"), "hello-world")?;
+        let link = temp_dir.path().join("link");
+        symlink(target_dir.path(), &link)?;
+
+        // Act
+        let paths = walk_dir(temp_dir.path())?.collect::<Result<Vec<_>, _>>()?;
+
+        // Assert
+        assert_eq!(vec![link], paths);
+        Ok(())
+    }
+
+    #[test]
+    fn test_walk_dir_not_found_fails() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("aaa");
+
+        // Act
+        let Err(e) = walk_dir(&path) else {
+            panic!("walk_dir must fail")
+        };
+
+        // Assert
+        assert_eq!(FileReadErrorKind::NotFound, e.kind());
+        Ok(())
+    }
+
+    #[cfg(feature = "glob")]
+    #[test]
+    fn test_glob_star_succeeds() -> Result<()> {
+        use super::glob;
+
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let dir = temp_dir.path().join("src");
+        create_dir_all(dir.join("aaa"))?;
+        write(dir.join("lib.rs"), "")?;
+        write(dir.join("main.rs"), "")?;
+        write(dir.join("README.md"), "")?;
+        write(dir.join("aaa").join("mod.rs"), "")?;
+
+        // Act
+        let paths = glob(temp_dir.path(), "src/*.rs")?;
+
+        // Assert
+        assert_eq!(vec![dir.join("lib.rs"), dir.join("main.rs")], paths);
+        Ok(())
+    }
+
+    #[cfg(feature = "glob")]
+    #[test]
+    fn test_glob_recursive_succeeds() -> Result<()> {
+        use super::glob;
+
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let dir = temp_dir.path().join("src");
+        create_dir_all(dir.join("aaa").join("bbb"))?;
+        write(dir.join("lib.rs"), "")?;
+        write(dir.join("README.md"), "")?;
+        write(dir.join("aaa").join("mod.rs"), "")?;
+        write(dir.join("aaa").join("bbb").join("mod.rs"), "")?;
+
+        // Act
+        let paths = glob(temp_dir.path(), "src/**/*.rs")?;
+
+        // Assert
+        assert_eq!(
+            vec![
+                dir.join("aaa").join("bbb").join("mod.rs"),
+                dir.join("aaa").join("mod.rs"),
+                dir.join("lib.rs"),
+            ],
+            paths
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "glob")]
+    #[test]
+    fn test_glob_invalid_pattern_fails() -> Result<()> {
+        use super::glob;
+
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+
+        // Act
+        let Err(e) = glob(temp_dir.path(), "src/***/*.rs") else {
+            panic!("glob must fail")
+        };
+
+        // Assert
+        assert_eq!(FileReadErrorKind::Other, e.kind());
+        assert!(format!("{e}").contains("Pattern syntax error"));
+        Ok(())
+    }
+}
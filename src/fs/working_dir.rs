@@ -20,30 +20,65 @@
 // WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
 //
 use std::env::{current_dir, set_current_dir};
-use std::io::Result as IOResult;
+use std::io::{ErrorKind as IOErrorKind, Result as IOResult};
 use std::path::{Path, PathBuf};
 
 pub struct WorkingDirectory {
     saved_dir: Option<PathBuf>,
+    fallback_dir: PathBuf,
 }
 
 #[allow(unused)]
 impl WorkingDirectory {
+    /// Changes the current directory to `dir` and restores the original
+    /// directory on close, falling back to the root of the original
+    /// directory's path if it no longer exists
     pub fn change(dir: &Path) -> IOResult<Self> {
+        let saved_dir = current_dir()?;
+        let fallback_dir = saved_dir
+            .ancestors()
+            .last()
+            .unwrap_or(&saved_dir)
+            .to_path_buf();
+        Self::change_with_fallback(dir, &fallback_dir)
+    }
+
+    /// Changes the current directory to `dir` and restores the original
+    /// directory on close, falling back to `fallback_dir` if it no longer
+    /// exists
+    pub fn change_with_fallback(dir: &Path, fallback_dir: &Path) -> IOResult<Self> {
         let saved_dir = current_dir()?;
         set_current_dir(dir)?;
         Ok(Self {
             saved_dir: Some(saved_dir),
+            fallback_dir: fallback_dir.to_path_buf(),
         })
     }
 
+    /// Restores the original directory: if it was deleted, the current
+    /// directory is changed to the fallback directory and the `NotFound`
+    /// error is still returned so that the caller knows
     pub fn close(&mut self) -> IOResult<()> {
         if let Some(ref d) = self.saved_dir {
-            set_current_dir(d)?;
+            match set_current_dir(d) {
+                Ok(()) => {}
+                Err(e) if e.kind() == IOErrorKind::NotFound => {
+                    set_current_dir(&self.fallback_dir)?;
+                    self.saved_dir = None;
+                    return Err(e);
+                }
+                Err(e) => return Err(e),
+            }
             self.saved_dir = None;
-        };
+        }
         Ok(())
     }
+
+    /// Leaves the current directory as it is: neither `close` nor drop
+    /// will change the directory after this is called
+    pub fn disarm(&mut self) {
+        self.saved_dir = None;
+    }
 }
 
 impl Drop for WorkingDirectory {
@@ -58,7 +93,9 @@ mod tests {
     use super::WorkingDirectory;
     use anyhow::Result;
     use serial_test::serial;
-    use std::env::current_dir;
+    use std::env::{current_dir, set_current_dir};
+    use std::fs::{create_dir, remove_dir};
+    use std::io::ErrorKind as IOErrorKind;
     use std::path::{Path, PathBuf};
     use tempdir::TempDir;
 
@@ -108,4 +145,60 @@ mod tests {
         assert_eq!(normalize_dir(&original_dir), normalize_dir(&current_dir()?));
         Ok(())
     }
+
+    #[test]
+    #[serial]
+    fn test_drop_saved_dir_deleted() -> Result<()> {
+        let original_dir = current_dir()?;
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let saved_dir = temp_dir.path().join("saved");
+        let dir = temp_dir.path().join("dir");
+        let fallback_dir = temp_dir.path().join("fallback");
+        for d in [&saved_dir, &dir, &fallback_dir] {
+            create_dir(d)?;
+        }
+        set_current_dir(&saved_dir)?;
+        let working_dir = WorkingDirectory::change_with_fallback(&dir, &fallback_dir)?;
+        remove_dir(&saved_dir)?;
+        drop(working_dir);
+        let result_dir = current_dir()?;
+        set_current_dir(&original_dir)?;
+        assert_eq!(normalize_dir(&fallback_dir), normalize_dir(&result_dir));
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_close_saved_dir_deleted_fails() -> Result<()> {
+        let original_dir = current_dir()?;
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let saved_dir = temp_dir.path().join("saved");
+        create_dir(&saved_dir)?;
+        set_current_dir(&saved_dir)?;
+        let mut working_dir =
+            WorkingDirectory::change_with_fallback(temp_dir.path(), temp_dir.path())?;
+        remove_dir(&saved_dir)?;
+        let result = working_dir.close();
+        set_current_dir(&original_dir)?;
+        assert_eq!(
+            IOErrorKind::NotFound,
+            result.expect_err("close must fail").kind()
+        );
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_disarm_then_drop() -> Result<()> {
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let original_dir = current_dir()?;
+        assert_ne!(normalize_dir(temp_dir.path()), normalize_dir(&original_dir));
+        let mut working_dir = WorkingDirectory::change(temp_dir.path())?;
+        working_dir.disarm();
+        drop(working_dir);
+        let result_dir = current_dir()?;
+        set_current_dir(&original_dir)?;
+        assert_eq!(normalize_dir(temp_dir.path()), normalize_dir(&result_dir));
+        Ok(())
+    }
 }
@@ -19,14 +19,25 @@
 // OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION
 // WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
 //
+use super::backup::safe_back_up;
+use super::paths::extended_length_path;
+use super::read::FileReadError;
 use crate::error::HasOtherError;
 use anyhow::Error as AnyhowError;
+use std::any::Any;
 use std::error::Error as StdError;
 use std::fmt::{Debug, Display};
-use std::fs::{create_dir_all, write, File, OpenOptions};
-use std::io::{Error as IOError, Write};
+use std::fs::{
+    create_dir_all, hard_link, metadata, remove_dir_all, remove_file, rename, set_permissions,
+    symlink_metadata, write, File, OpenOptions,
+};
+use std::io::{Error as IOError, ErrorKind as IOErrorKind, Result as IOResult, Write};
+use std::mem::ManuallyDrop;
 use std::path::{Path, PathBuf};
+use std::process;
 use std::result::Result as StdResult;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
 use thiserror::Error;
 
 #[allow(unused)]
@@ -34,10 +45,40 @@ use thiserror::Error;
 #[non_exhaustive]
 pub enum FileWriteErrorKind {
     AlreadyExists,
+    NotFound,
     Other,
 }
 
-#[derive(Debug, Error)]
+#[allow(unused)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    #[allow(unused)]
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Lf => "\n",
+            Self::CrLf => "\r\n",
+        }
+    }
+
+    /// Rewrites all line endings in `s`, including lone `\r`, to this style
+    #[allow(unused)]
+    #[must_use]
+    pub fn normalize(self, s: &str) -> String {
+        let lf = s.replace("\r\n", "\n").replace('\r', "\n");
+        match self {
+            Self::Lf => lf,
+            Self::CrLf => lf.replace('\n', self.as_str()),
+        }
+    }
+}
+
+#[derive(Debug, Error, PartialEq)]
 #[error(transparent)]
 pub struct FileWriteError(#[from] FileWriteErrorImpl);
 
@@ -47,33 +88,72 @@ impl FileWriteError {
     pub const fn kind(&self) -> FileWriteErrorKind {
         match self.0 {
             FileWriteErrorImpl::AlreadyExists(_) => FileWriteErrorKind::AlreadyExists,
+            FileWriteErrorImpl::NotFound(_) => FileWriteErrorKind::NotFound,
             _ => FileWriteErrorKind::Other,
         }
     }
 
+    /// Returns whether the error is of the given kind
+    #[allow(unused)]
+    #[allow(clippy::needless_pass_by_value)]
+    #[must_use]
+    pub fn is_kind(&self, kind: FileWriteErrorKind) -> bool {
+        self.kind() == kind
+    }
+
     #[allow(unused)]
     #[must_use]
     pub fn is_already_exists(&self) -> bool {
         self.kind() == FileWriteErrorKind::AlreadyExists
     }
 
+    #[allow(unused)]
+    #[must_use]
+    pub fn is_not_found(&self) -> bool {
+        self.kind() == FileWriteErrorKind::NotFound
+    }
+
     #[allow(unused)]
     #[must_use]
     pub fn is_other(&self) -> bool {
         self.kind() == FileWriteErrorKind::Other
     }
 
-    fn other<E>(e: E) -> Self
+    /// Returns the path associated with the error, if any
+    #[allow(unused)]
+    #[must_use]
+    pub fn path(&self) -> Option<&Path> {
+        match self.0 {
+            FileWriteErrorImpl::AlreadyExists(ref p) | FileWriteErrorImpl::NotFound(ref p) => {
+                Some(p)
+            }
+            FileWriteErrorImpl::Other(_) => None,
+        }
+    }
+
+    pub(super) fn other<E>(e: E) -> Self
     where
         E: StdError + Send + Sync + 'static,
     {
         Self(FileWriteErrorImpl::Other(AnyhowError::new(e)))
     }
 
-    fn convert(e: IOError, path: &Path) -> Self {
+    // Preserves the classification of a missing source file read as part
+    // of a write operation
+    pub(super) fn convert_read(e: FileReadError) -> Self {
+        if e.is_not_found() {
+            if let Some(path) = e.path() {
+                return Self(FileWriteErrorImpl::NotFound(path.to_path_buf()));
+            }
+        }
+        Self::other(e)
+    }
+
+    pub(super) fn convert(e: IOError, path: &Path) -> Self {
         use std::io::ErrorKind::*;
         match e.kind() {
             AlreadyExists => Self(FileWriteErrorImpl::AlreadyExists(path.to_path_buf())),
+            NotFound => Self(FileWriteErrorImpl::NotFound(path.to_path_buf())),
             _ => Self::other(e),
         }
     }
@@ -84,78 +164,526 @@ impl HasOtherError for FileWriteError {
         self.is_other()
     }
 
-    fn downcast_other_ref<E>(&self) -> Option<&E>
-    where
-        E: Display + Debug + Send + Sync + 'static,
-    {
+    fn downcast_other_any(&self) -> Option<&dyn Any> {
         if let FileWriteErrorImpl::Other(ref inner) = self.0 {
-            inner.downcast_ref::<E>()
+            Some(inner)
         } else {
             None
         }
     }
+
+    fn downcast_other<E>(self) -> StdResult<E, Self>
+    where
+        E: Display + Debug + Send + Sync + 'static,
+    {
+        match self.0 {
+            FileWriteErrorImpl::Other(inner) => inner
+                .downcast::<E>()
+                .map_err(|inner| Self(FileWriteErrorImpl::Other(inner))),
+            inner => Err(Self(inner)),
+        }
+    }
 }
 
 #[derive(Debug, Error)]
 enum FileWriteErrorImpl {
     #[error("File {0} already exists")]
     AlreadyExists(PathBuf),
+    #[error("File system object {0} not found")]
+    NotFound(PathBuf),
     #[error(transparent)]
     Other(AnyhowError),
 }
 
+impl PartialEq for FileWriteErrorImpl {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::AlreadyExists(a), Self::AlreadyExists(b))
+            | (Self::NotFound(a), Self::NotFound(b)) => a == b,
+            (Self::Other(_), Self::Other(_)) => true,
+            _ => false,
+        }
+    }
+}
+
 #[allow(unused)]
-pub fn safe_create_file(path: &Path, overwrite: bool) -> StdResult<File, FileWriteError> {
+pub fn safe_create_file(
+    path: impl AsRef<Path>,
+    overwrite: bool,
+) -> StdResult<File, FileWriteError> {
+    let path = path.as_ref();
     ensure_dir(path)?;
 
     let mut options = OpenOptions::new();
     options.write(true);
     if overwrite {
-        options.create(true);
+        options.create(true).truncate(false);
     } else {
         options.create_new(true);
     }
 
     options
-        .open(path)
+        .open(extended_length_path(path))
+        .map_err(|e| FileWriteError::convert(e, path))
+}
+
+/// Appends `contents` to the file at `path`, creating the file if it does
+/// not exist
+///
+/// The file is opened in append mode so every write lands at the end of
+/// the file, but `contents` may be written in more than one write so
+/// output from concurrent appenders can interleave
+#[allow(unused)]
+pub fn append_file<C>(path: impl AsRef<Path>, contents: C) -> StdResult<(), FileWriteError>
+where
+    C: AsRef<[u8]>,
+{
+    let path = path.as_ref();
+    ensure_dir(path)?;
+    OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(extended_length_path(path))
+        .and_then(|mut f| f.write_all(contents.as_ref()))
         .map_err(|e| FileWriteError::convert(e, path))
 }
 
 #[allow(unused)]
 pub fn safe_write_file<C>(
-    path: &Path,
+    path: impl AsRef<Path>,
     contents: C,
     overwrite: bool,
 ) -> StdResult<(), FileWriteError>
 where
     C: AsRef<[u8]>,
 {
-    ensure_dir(path)?;
+    let path = path.as_ref();
+    WriteOptions::new()
+        .overwrite(overwrite)
+        .write(path, contents)
+}
 
-    if overwrite {
-        write(path, contents).map_err(|e| FileWriteError::convert(e, path))?;
+/// Options controlling how `WriteOptions::write` writes a file
+///
+/// By default an existing file is not overwritten and missing parent
+/// directories are created
+#[allow(unused, clippy::struct_excessive_bools)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct WriteOptions {
+    overwrite: bool,
+    atomic: bool,
+    backup: bool,
+    create_parents: bool,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[allow(unused)]
+impl WriteOptions {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            overwrite: false,
+            atomic: false,
+            backup: false,
+            create_parents: true,
+        }
+    }
+
+    /// Replaces an existing file instead of failing
+    #[must_use]
+    pub const fn overwrite(mut self, value: bool) -> Self {
+        self.overwrite = value;
+        self
+    }
+
+    /// Writes to a temporary file in the same directory and renames it
+    /// over the destination so that readers never see a partial file
+    ///
+    /// Without `overwrite` the temporary file is hard-linked into place
+    /// instead, so a file created at the destination during the write is
+    /// never replaced; on file systems without hard links this falls back
+    /// to a rename and the check is only best-effort
+    #[must_use]
+    pub const fn atomic(mut self, value: bool) -> Self {
+        self.atomic = value;
+        self
+    }
+
+    /// Backs up an existing file using `safe_back_up` before replacing it
+    #[must_use]
+    pub const fn backup(mut self, value: bool) -> Self {
+        self.backup = value;
+        self
+    }
+
+    /// Creates missing parent directories of the destination
+    #[must_use]
+    pub const fn create_parents(mut self, value: bool) -> Self {
+        self.create_parents = value;
+        self
+    }
+
+    pub fn write<C>(&self, path: impl AsRef<Path>, contents: C) -> StdResult<(), FileWriteError>
+    where
+        C: AsRef<[u8]>,
+    {
+        let path = path.as_ref();
+        if self.create_parents {
+            ensure_dir(path)?;
+        }
+
+        let exists = extended_length_path(path).is_file();
+        if exists && !self.overwrite {
+            return Err(FileWriteError(FileWriteErrorImpl::AlreadyExists(
+                path.to_path_buf(),
+            )));
+        }
+
+        if exists && self.backup {
+            safe_back_up(path).map_err(|e| FileWriteError::convert(e, path))?;
+        }
+
+        if self.atomic {
+            let dir = match path.parent() {
+                Some(d) if !d.as_os_str().is_empty() => d,
+                _ => Path::new("."),
+            };
+            // TempFile creates missing directories so check explicitly
+            if !self.create_parents && !extended_length_path(dir).is_dir() {
+                return Err(FileWriteError::convert(
+                    IOError::from(IOErrorKind::NotFound),
+                    path,
+                ));
+            }
+            let mut temp_file = TempFile::new(dir)?;
+            if exists {
+                copy_ownership_and_permissions(path, temp_file.file())
+                    .map_err(|e| FileWriteError::convert(e, temp_file.path()))?;
+            }
+            temp_file
+                .file_mut()
+                .write_all(contents.as_ref())
+                .and_then(|()| temp_file.file().sync_all())
+                .map_err(|e| FileWriteError::convert(e, temp_file.path()))?;
+            persist(temp_file, path, self.overwrite)?;
+        } else if self.overwrite {
+            write(extended_length_path(path), contents)
+                .map_err(|e| FileWriteError::convert(e, path))?;
+        } else {
+            let mut file = OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(extended_length_path(path))
+                .map_err(|e| FileWriteError::convert(e, path))?;
+            file.write_all(contents.as_ref())
+                .map_err(|e| FileWriteError::convert(e, path))?;
+        }
+
+        Ok(())
+    }
+}
+
+// Moves temp_file into place at path: unless overwrite is set, the file is
+// hard-linked into place so that a file created at path since the caller
+// checked for one is never replaced, falling back to a rename on file
+// systems without hard links
+fn persist(temp_file: TempFile, path: &Path, overwrite: bool) -> StdResult<(), FileWriteError> {
+    let p = extended_length_path(path);
+    if !overwrite {
+        match hard_link(temp_file.path(), &p) {
+            Ok(()) => return Ok(()),
+            Err(e) if e.kind() == IOErrorKind::AlreadyExists => {
+                return Err(FileWriteError::convert(e, path));
+            }
+            Err(_) => {}
+        }
+    }
+    rename(temp_file.path(), &p).map_err(|e| FileWriteError::convert(e, path))?;
+    _ = temp_file.keep();
+    Ok(())
+}
+
+// Applies the permissions and, where the process is allowed to change
+// them, the owner and group of the file at path to file
+fn copy_ownership_and_permissions(path: &Path, file: &File) -> IOResult<()> {
+    let m = metadata(extended_length_path(path))?;
+
+    // Change ownership first since doing so may clear set-user-ID and
+    // set-group-ID bits
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{fchown, MetadataExt};
+        _ = fchown(file, Some(m.uid()), Some(m.gid()));
+    }
+
+    file.set_permissions(m.permissions())
+}
+
+/// Removes the directory at `path` and all of its contents, succeeding
+/// without doing anything if the directory does not exist and
+/// `missing_ok` is set
+#[allow(unused)]
+pub fn safe_remove_dir_all(
+    path: impl AsRef<Path>,
+    missing_ok: bool,
+) -> StdResult<(), FileWriteError> {
+    let path = path.as_ref();
+    match remove_dir_all(extended_length_path(path)) {
+        Ok(()) => Ok(()),
+        Err(e) if missing_ok && e.kind() == IOErrorKind::NotFound => Ok(()),
+        Err(e) => Err(FileWriteError::convert(e, path)),
+    }
+}
+
+/// Writes `contents` to `path`, appending a newline if non-empty
+/// `contents` does not already end with one
+#[allow(unused)]
+pub fn safe_write_text_file(
+    path: impl AsRef<Path>,
+    contents: &str,
+    overwrite: bool,
+) -> StdResult<(), FileWriteError> {
+    let path = path.as_ref();
+    if contents.is_empty() || contents.ends_with('\n') {
+        safe_write_file(path, contents, overwrite)
     } else {
-        let mut file = safe_create_file(path, overwrite)?;
-        file.write_all(contents.as_ref())
-            .map_err(|e| FileWriteError::convert(e, path))?;
+        safe_write_file(path, format!("{contents}\n"), overwrite)
     }
+}
 
+/// Writes `contents` to `path` and then applies the permissions of
+/// `template` to it
+#[allow(unused)]
+pub fn safe_write_file_like(
+    path: impl AsRef<Path>,
+    contents: &[u8],
+    template: impl AsRef<Path>,
+    overwrite: bool,
+) -> StdResult<(), FileWriteError> {
+    let path = path.as_ref();
+    let template = template.as_ref();
+    let permissions = metadata(extended_length_path(template))
+        .map_err(|e| FileWriteError::convert_read(FileReadError::convert(e, template)))?
+        .permissions();
+    safe_write_file(path, contents, overwrite)?;
+    set_permissions(extended_length_path(path), permissions)
+        .map_err(|e| FileWriteError::convert(e, path))?;
     Ok(())
 }
 
-fn ensure_dir(file_path: &Path) -> StdResult<(), FileWriteError> {
+#[allow(unused)]
+pub fn safe_write_file_normalized(
+    path: impl AsRef<Path>,
+    contents: &str,
+    line_ending: LineEnding,
+    overwrite: bool,
+) -> StdResult<(), FileWriteError> {
+    let path = path.as_ref();
+    safe_write_file(path, line_ending.normalize(contents), overwrite)
+}
+
+/// A uniquely named file that is deleted when dropped unless `keep` is
+/// called
+pub struct TempFile {
+    path: PathBuf,
+    file: ManuallyDrop<File>,
+    keep: bool,
+}
+
+#[allow(unused)]
+impl TempFile {
+    pub fn new(dir: impl AsRef<Path>) -> StdResult<Self, FileWriteError> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let dir = dir.as_ref();
+
+        loop {
+            let nanos = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.subsec_nanos())
+                .unwrap_or_default();
+            let path = dir.join(format!(
+                ".tmp-{}-{}-{nanos}",
+                process::id(),
+                COUNTER.fetch_add(1, Ordering::Relaxed)
+            ));
+            match safe_create_file(&path, false) {
+                Ok(file) => {
+                    return Ok(Self {
+                        path,
+                        file: ManuallyDrop::new(file),
+                        keep: false,
+                    })
+                }
+                Err(e) if e.is_already_exists() => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    #[must_use]
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+
+    pub fn file_mut(&mut self) -> &mut File {
+        &mut self.file
+    }
+
+    /// Closes the file and retains it on disk, returning its path
+    #[must_use]
+    pub fn keep(mut self) -> PathBuf {
+        self.keep = true;
+        self.path.clone()
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        // Close the file first since Windows cannot delete an open file
+        // SAFETY: self.file is not accessed again after this point
+        unsafe { ManuallyDrop::drop(&mut self.file) };
+        if !self.keep {
+            _ = remove_file(&self.path);
+        }
+    }
+}
+
+/// Writes a file incrementally via a temporary file in the same
+/// directory that replaces the destination atomically on `commit`
+///
+/// Dropping the writer without calling `commit` discards the temporary
+/// file and leaves the destination untouched
+pub struct SafeWriter {
+    path: PathBuf,
+    overwrite: bool,
+    temp_file: TempFile,
+}
+
+#[allow(unused)]
+impl SafeWriter {
+    /// Creates a writer for `path`, creating missing parent directories
+    /// and failing if `path` already exists and `overwrite` is not set
+    pub fn new(path: impl AsRef<Path>, overwrite: bool) -> StdResult<Self, FileWriteError> {
+        let path = path.as_ref();
+        ensure_dir(path)?;
+        if !overwrite && extended_length_path(path).exists() {
+            return Err(FileWriteError(FileWriteErrorImpl::AlreadyExists(
+                path.to_path_buf(),
+            )));
+        }
+
+        let dir = match path.parent() {
+            Some(d) if !d.as_os_str().is_empty() => d,
+            _ => Path::new("."),
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            overwrite,
+            temp_file: TempFile::new(dir)?,
+        })
+    }
+
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn write_all(&mut self, buf: &[u8]) -> StdResult<(), FileWriteError> {
+        self.temp_file
+            .file_mut()
+            .write_all(buf)
+            .map_err(|e| FileWriteError::convert(e, self.temp_file.path()))
+    }
+
+    pub fn flush(&mut self) -> StdResult<(), FileWriteError> {
+        self.temp_file
+            .file_mut()
+            .flush()
+            .map_err(|e| FileWriteError::convert(e, self.temp_file.path()))
+    }
+
+    /// Syncs the written data to disk and moves it into place, keeping
+    /// the permissions of any file it replaces
+    pub fn commit(self) -> StdResult<(), FileWriteError> {
+        if self.overwrite && extended_length_path(&self.path).is_file() {
+            copy_ownership_and_permissions(&self.path, self.temp_file.file())
+                .map_err(|e| FileWriteError::convert(e, self.temp_file.path()))?;
+        }
+        self.temp_file
+            .file()
+            .sync_all()
+            .map_err(|e| FileWriteError::convert(e, self.temp_file.path()))?;
+        persist(self.temp_file, &self.path, self.overwrite)
+    }
+
+    /// Discards the written data, leaving the destination untouched
+    pub fn abort(self) {}
+}
+
+/// Creates a symbolic link at `link` pointing to `target`, creating the
+/// parent directory of `link` if necessary and replacing an existing
+/// link or file if `overwrite` is set
+#[allow(unused)]
+pub fn safe_symlink(
+    target: impl AsRef<Path>,
+    link: impl AsRef<Path>,
+    overwrite: bool,
+) -> StdResult<(), FileWriteError> {
+    #[cfg(unix)]
+    use std::os::unix::fs::symlink;
+    #[cfg(windows)]
+    use std::os::windows::fs::symlink_file as symlink;
+
+    let target = target.as_ref();
+    let link = link.as_ref();
+
+    ensure_dir(link)?;
+
+    let link_path = extended_length_path(link);
+    if overwrite && symlink_metadata(&link_path).is_ok_and(|m| !m.file_type().is_dir()) {
+        remove_file(&link_path).map_err(|e| FileWriteError::convert(e, link))?;
+    }
+
+    symlink(target, &link_path).map_err(|e| FileWriteError::convert(e, link))
+}
+
+/// Creates the parent directory of `file_path`, if it does not already
+/// exist, and returns its path
+#[allow(unused)]
+pub fn ensure_parent_dir(file_path: impl AsRef<Path>) -> StdResult<PathBuf, FileWriteError> {
+    let file_path = file_path.as_ref();
     let mut dir = PathBuf::new();
     dir.push(file_path);
     dir.pop();
-    create_dir_all(&dir).map_err(FileWriteError::other)?;
+    create_dir_all(extended_length_path(&dir)).map_err(FileWriteError::other)?;
+    Ok(dir)
+}
+
+fn ensure_dir(file_path: &Path) -> StdResult<(), FileWriteError> {
+    ensure_parent_dir(file_path)?;
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{safe_create_file, safe_write_file, FileWriteErrorKind};
+    use super::{
+        ensure_parent_dir, persist, safe_create_file, safe_remove_dir_all, safe_symlink,
+        safe_write_file, safe_write_file_like, safe_write_file_normalized, safe_write_text_file,
+        FileWriteErrorKind, LineEnding, SafeWriter, TempFile, WriteOptions,
+    };
     use anyhow::Result;
-    use std::fs::{read_to_string, write};
+    use std::fs::{create_dir_all, read_dir, read_to_string, write};
     use std::io::Write;
     use tempdir::TempDir;
 
@@ -293,4 +821,599 @@ mod tests {
         assert_eq!("something-else", read_to_string(&path)?);
         Ok(())
     }
+
+    #[test]
+    fn test_safe_remove_dir_all_succeeds() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let dir = temp_dir.path().join("scratch");
+        create_dir_all(dir.join("aaa").join("bbb"))?;
+        write(dir.join("file0.txt"), "hello")?;
+        write(dir.join("aaa").join("bbb").join("file1.txt"), "world")?;
+
+        // Act
+        safe_remove_dir_all(&dir, false)?;
+
+        // Assert
+        assert!(!dir.exists());
+        assert!(temp_dir.path().is_dir());
+        Ok(())
+    }
+
+    #[test]
+    fn test_safe_remove_dir_all_missing_ok_succeeds() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let dir = temp_dir.path().join("scratch");
+
+        // Act
+        safe_remove_dir_all(&dir, true)?;
+
+        // Assert
+        assert!(!dir.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_safe_remove_dir_all_missing_fails() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let dir = temp_dir.path().join("scratch");
+
+        // Act
+        let Err(e) = safe_remove_dir_all(&dir, false) else {
+            panic!("safe_remove_dir_all must fail")
+        };
+
+        // Assert
+        assert_eq!(FileWriteErrorKind::NotFound, e.kind());
+        assert!(e.is_not_found());
+        assert_eq!(Some(dir.as_path()), e.path());
+        Ok(())
+    }
+
+    #[test]
+    fn test_safe_write_text_file_appends_newline() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.txt");
+
+        // Act
+        safe_write_text_file(&path, "hello-world", false)?;
+
+        // Assert
+        assert_eq!("hello-world\n", read_to_string(&path)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_safe_write_text_file_existing_newline_unchanged() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.txt");
+
+        // Act
+        safe_write_text_file(&path, "hello-world\n", false)?;
+
+        // Assert
+        assert_eq!("hello-world\n", read_to_string(&path)?);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_safe_write_file_like_succeeds() -> Result<()> {
+        use std::fs::{metadata, set_permissions, Permissions};
+        use std::os::unix::fs::PermissionsExt;
+
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let template_path = temp_dir.path().join("template.txt");
+        let path = temp_dir.path().join("file.txt");
+        write(&template_path, "template")?;
+        set_permissions(&template_path, Permissions::from_mode(0o600))?;
+
+        // Act
+        safe_write_file_like(&path, b"hello-world", &template_path, false)?;
+
+        // Assert
+        assert_eq!("hello-world", read_to_string(&path)?);
+        assert_eq!(0o600, metadata(&path)?.permissions().mode() & 0o777);
+        Ok(())
+    }
+
+    #[test]
+    fn test_safe_write_file_like_missing_template_fails() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let template_path = temp_dir.path().join("template.txt");
+        let path = temp_dir.path().join("file.txt");
+
+        // Act
+        let Err(e) = safe_write_file_like(&path, b"hello-world", &template_path, false) else {
+            panic!("safe_write_file_like must fail")
+        };
+
+        // Assert
+        assert!(e.is_not_found());
+        assert!(!path.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_safe_write_file_normalized_lf_succeeds() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.txt");
+
+        // Act
+        safe_write_file_normalized(&path, "aaa\r\nbbb\rccc\nddd", LineEnding::Lf, false)?;
+
+        // Assert
+        assert_eq!("aaa\nbbb\nccc\nddd", read_to_string(&path)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_safe_write_file_normalized_crlf_succeeds() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.txt");
+
+        // Act
+        safe_write_file_normalized(&path, "aaa\r\nbbb\rccc\nddd\n", LineEnding::CrLf, false)?;
+
+        // Assert
+        assert_eq!("aaa\r\nbbb\r\nccc\r\nddd\r\n", read_to_string(&path)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_ensure_parent_dir_succeeds() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let dir = temp_dir.path().join("aaa").join("bbb");
+        let path = dir.join("file.txt");
+
+        // Act
+        let result = ensure_parent_dir(&path)?;
+
+        // Assert
+        assert_eq!(dir, result);
+        assert!(result.is_dir());
+        assert!(!path.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_write_error_eq() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.txt");
+        let other_path = temp_dir.path().join("other.txt");
+        write(&path, "hello-world")?;
+        write(&other_path, "hello-world")?;
+
+        // Act
+        let Err(e0) = safe_write_file(&path, "something-else", false) else {
+            panic!("safe_write_file must fail")
+        };
+        let Err(e1) = safe_create_file(&path, false) else {
+            panic!("safe_create_file must fail")
+        };
+        let Err(e2) = safe_create_file(&other_path, false) else {
+            panic!("safe_create_file must fail")
+        };
+
+        // Assert
+        assert_eq!(e0, e1);
+        assert_ne!(e0, e2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_temp_file_drop_deletes() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let mut temp_file = TempFile::new(temp_dir.path())?;
+        temp_file.file_mut().write_all(b"hello-world")?;
+        let path = temp_file.path().to_path_buf();
+        assert!(path.is_file());
+
+        // Act
+        drop(temp_file);
+
+        // Assert
+        assert!(!path.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_temp_file_keep_retains() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let mut temp_file = TempFile::new(temp_dir.path())?;
+        temp_file.file_mut().write_all(b"hello-world")?;
+        let other_temp_file = TempFile::new(temp_dir.path())?;
+        assert_ne!(temp_file.path(), other_temp_file.path());
+
+        // Act
+        let path = temp_file.keep();
+
+        // Assert
+        assert_eq!("hello-world", read_to_string(&path)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_safe_writer_commit_succeeds() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("aaa").join("file.txt");
+        let mut writer = SafeWriter::new(&path, false)?;
+
+        // Act
+        writer.write_all(b"first,")?;
+        writer.write_all(b"second,")?;
+        writer.flush()?;
+        assert!(!path.exists());
+        writer.write_all(b"third")?;
+        writer.commit()?;
+
+        // Assert
+        assert_eq!("first,second,third", read_to_string(&path)?);
+        assert_eq!(1, read_dir(temp_dir.path().join("aaa"))?.count());
+        Ok(())
+    }
+
+    #[test]
+    fn test_safe_writer_drop_without_commit() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.txt");
+        let mut writer = SafeWriter::new(&path, false)?;
+        writer.write_all(b"hello-world")?;
+
+        // Act
+        drop(writer);
+
+        // Assert
+        assert!(!path.exists());
+        assert_eq!(0, read_dir(temp_dir.path())?.count());
+        Ok(())
+    }
+
+    #[test]
+    fn test_safe_writer_abort_keeps_existing() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.txt");
+        write(&path, "first")?;
+        let mut writer = SafeWriter::new(&path, true)?;
+        writer.write_all(b"second")?;
+
+        // Act
+        writer.abort();
+
+        // Assert
+        assert_eq!("first", read_to_string(&path)?);
+        assert_eq!(1, read_dir(temp_dir.path())?.count());
+        Ok(())
+    }
+
+    #[test]
+    fn test_safe_writer_exists_no_overwrite_fails() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.txt");
+        write(&path, "first")?;
+
+        // Act
+        let Err(e) = SafeWriter::new(&path, false) else {
+            panic!("SafeWriter::new must fail")
+        };
+
+        // Assert
+        assert_eq!(FileWriteErrorKind::AlreadyExists, e.kind());
+        assert_eq!("first", read_to_string(&path)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_safe_writer_created_before_commit_no_overwrite_fails() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.txt");
+        let mut writer = SafeWriter::new(&path, false)?;
+        writer.write_all(b"second")?;
+        write(&path, "first")?;
+
+        // Act
+        let Err(e) = writer.commit() else {
+            panic!("commit must fail")
+        };
+
+        // Assert
+        assert_eq!(FileWriteErrorKind::AlreadyExists, e.kind());
+        assert_eq!("first", read_to_string(&path)?);
+        assert_eq!(1, read_dir(temp_dir.path())?.count());
+        Ok(())
+    }
+
+    #[test]
+    fn test_persist_no_overwrite_keeps_existing() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.txt");
+        let mut temp_file = TempFile::new(temp_dir.path())?;
+        temp_file.file_mut().write_all(b"second")?;
+        write(&path, "first")?;
+
+        // Act
+        let Err(e) = persist(temp_file, &path, false) else {
+            panic!("persist must fail")
+        };
+
+        // Assert
+        assert_eq!(FileWriteErrorKind::AlreadyExists, e.kind());
+        assert_eq!("first", read_to_string(&path)?);
+        assert_eq!(1, read_dir(temp_dir.path())?.count());
+        Ok(())
+    }
+
+    #[test]
+    fn test_persist_no_overwrite_succeeds() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.txt");
+        let mut temp_file = TempFile::new(temp_dir.path())?;
+        temp_file.file_mut().write_all(b"hello-world")?;
+
+        // Act
+        persist(temp_file, &path, false)?;
+
+        // Assert
+        assert_eq!("hello-world", read_to_string(&path)?);
+        assert_eq!(1, read_dir(temp_dir.path())?.count());
+        Ok(())
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_safe_write_file_long_path_succeeds() -> Result<()> {
+        use crate::fs::read_text_file;
+
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let mut path = temp_dir.path().to_path_buf();
+        for _ in 0..20 {
+            path.push("abcdefghijklmnopqrstuvwxyz");
+        }
+        path.push("file.txt");
+        assert!(path.as_os_str().len() > 260);
+
+        // Act
+        safe_write_file(&path, "hello-world", false)?;
+
+        // Assert
+        assert_eq!("hello-world", read_text_file(&path)?);
+        let Err(e) = safe_write_file(&path, "something-else", false) else {
+            panic!("safe_write_file must fail")
+        };
+        let message = format!("{e}");
+        assert!(!message.contains(r"\\?\"));
+        assert!(message.contains(path.to_str().expect("must be valid string")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_options_defaults_do_not_overwrite() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("aaa").join("file.txt");
+        WriteOptions::new().write(&path, "first")?;
+
+        // Act
+        let Err(e) = WriteOptions::new().write(&path, "second") else {
+            panic!("write must fail")
+        };
+
+        // Assert
+        assert_eq!(FileWriteErrorKind::AlreadyExists, e.kind());
+        assert_eq!("first", read_to_string(&path)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_options_atomic_backup() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.txt");
+        write(&path, "first")?;
+
+        // Act
+        WriteOptions::new()
+            .overwrite(true)
+            .atomic(true)
+            .backup(true)
+            .write(&path, "second")?;
+
+        // Assert
+        assert_eq!("second", read_to_string(&path)?);
+        let mut backups = Vec::new();
+        for entry in read_dir(temp_dir.path())? {
+            let entry_path = entry?.path();
+            if entry_path != path {
+                backups.push(entry_path);
+            }
+        }
+        assert_eq!(1, backups.len());
+        assert_eq!("first", read_to_string(&backups[0])?);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_options_atomic_preserves_permissions() -> Result<()> {
+        use std::fs::{metadata, set_permissions, Permissions};
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("secret.txt");
+        write(&path, "first")?;
+        set_permissions(&path, Permissions::from_mode(0o600))?;
+        let original = metadata(&path)?;
+
+        // Act
+        WriteOptions::new()
+            .overwrite(true)
+            .atomic(true)
+            .write(&path, "second")?;
+
+        // Assert
+        assert_eq!("second", read_to_string(&path)?);
+        let m = metadata(&path)?;
+        assert_ne!(original.ino(), m.ino());
+        assert_eq!(0o600, m.permissions().mode() & 0o777);
+        assert_eq!(original.uid(), m.uid());
+        assert_eq!(original.gid(), m.gid());
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_options_no_create_parents_fails() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("aaa").join("file.txt");
+
+        for atomic in [false, true] {
+            // Act
+            let Err(e) = WriteOptions::new()
+                .create_parents(false)
+                .atomic(atomic)
+                .write(&path, "first")
+            else {
+                panic!("write must fail")
+            };
+
+            // Assert
+            assert!(e.is_not_found());
+            assert!(!temp_dir.path().join("aaa").exists());
+        }
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_safe_symlink_succeeds() -> Result<()> {
+        use std::fs::read_link;
+
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let target = temp_dir.path().join("target.txt");
+        let link = temp_dir.path().join("aaa").join("link.txt");
+        write(&target, "hello-world")?;
+
+        // Act
+        safe_symlink(&target, &link, false)?;
+
+        // Assert
+        assert_eq!(target, read_link(&link)?);
+        assert_eq!("hello-world", read_to_string(&link)?);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_safe_symlink_no_overwrite_fails() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let target = temp_dir.path().join("target.txt");
+        let link = temp_dir.path().join("link.txt");
+        safe_symlink(&target, &link, false)?;
+
+        // Act
+        let Err(e) = safe_symlink(&target, &link, false) else {
+            panic!("safe_symlink must fail")
+        };
+
+        // Assert
+        assert_eq!(FileWriteErrorKind::AlreadyExists, e.kind());
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_safe_symlink_overwrite_succeeds() -> Result<()> {
+        use std::fs::read_link;
+
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let target0 = temp_dir.path().join("target0.txt");
+        let target1 = temp_dir.path().join("target1.txt");
+        let link = temp_dir.path().join("link.txt");
+        safe_symlink(&target0, &link, false)?;
+
+        // Act
+        safe_symlink(&target1, &link, true)?;
+
+        // Assert
+        assert_eq!(target1, read_link(&link)?);
+        Ok(())
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_safe_symlink_no_overwrite_fails() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let target = temp_dir.path().join("target.txt");
+        let link = temp_dir.path().join("link.txt");
+        write(&target, "hello-world")?;
+        write(&link, "existing")?;
+
+        // Act
+        let Err(e) = safe_symlink(&target, &link, false) else {
+            panic!("safe_symlink must fail")
+        };
+
+        // Assert
+        assert_eq!(FileWriteErrorKind::AlreadyExists, e.kind());
+        Ok(())
+    }
+
+    #[test]
+    fn test_path_already_exists() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.txt");
+        write(&path, "hello-world")?;
+
+        // Act
+        let Err(e) = safe_write_file(&path, "something-else", false) else {
+            panic!("safe_write_file must fail")
+        };
+
+        // Assert
+        assert_eq!(Some(path.as_path()), e.path());
+        Ok(())
+    }
+
+    #[test]
+    fn test_safe_write_file_as_ref_path() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new("joatmon-test")?;
+        let path = temp_dir.path().join("file.txt");
+        let s = path.to_str().expect("must be valid string");
+
+        // Act
+        safe_write_file(s, "aaa", false)?;
+        safe_write_file(String::from(s), "bbb", true)?;
+        safe_write_file(path.clone(), "ccc", true)?;
+
+        // Assert
+        assert_eq!("ccc", read_to_string(&path)?);
+        Ok(())
+    }
 }
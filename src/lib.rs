@@ -34,14 +34,61 @@
 mod error;
 mod formats;
 mod fs;
+#[cfg(feature = "test-util")]
+mod test_util;
 
-pub use self::error::HasOtherError;
+pub use self::error::{HasOtherError, HasOtherErrorExt};
+#[cfg(feature = "flate2")]
+pub use self::formats::read_json_gz_file;
 pub use self::formats::{
-    read_json_file, read_toml_file, read_toml_file_edit, read_yaml_file, JsonError, JsonErrorKind,
-    TomlError, TomlErrorKind, YamlError, YamlErrorKind,
+    append_ndjson, apply_json_patch, doc_get_str, doc_remove_path, json_diff, load, merge_toml,
+    offset_to_line_col, read_env_file, read_json_file, read_json_file_bounded,
+    read_json_file_deny_unknown, read_json_vec, read_layered_toml, read_layered_with_provenance,
+    read_toml_array_of_tables, read_toml_file, read_toml_file_both, read_toml_file_edit,
+    read_yaml_dir, read_yaml_file, read_yaml_tagged, require_keys, toml_datetime_to_chrono,
+    toml_set_in_file, value_get_path, write_json_array, write_json_file_compact,
+    write_json_file_durable, write_toml_file_sorted, write_yaml_documents, write_yaml_file_styled,
+    ConfigError, ConfigErrorKind, Format, FormatError, FormatErrorKind, HasPosition, Json,
+    JsonError, JsonErrorKind, Toml, TomlError, TomlErrorKind, Yaml, YamlError, YamlErrorKind,
 };
+#[cfg(feature = "csv")]
+pub use self::formats::{read_csv_file, CsvError, CsvErrorKind};
+#[cfg(feature = "ini")]
+pub use self::formats::{read_ini_file, IniError, IniErrorKind};
+#[cfg(feature = "jsonc")]
+pub use self::formats::{read_jsonc_preserving, JsoncDocument};
+#[cfg(feature = "msgpack")]
+pub use self::formats::{read_msgpack_file, write_msgpack_file, MsgpackError, MsgpackErrorKind};
+#[cfg(feature = "ron")]
+pub use self::formats::{read_ron_file, RonError, RonErrorKind};
+#[cfg(unix)]
+pub use self::fs::find_sentinel_file_same_device;
+#[cfg(feature = "glob")]
+pub use self::fs::glob;
+#[cfg(feature = "mmap")]
+pub use self::fs::map_file;
+#[cfg(feature = "filetime")]
+pub use self::fs::set_modified;
+#[cfg(feature = "similar")]
+pub use self::fs::text_file_diff;
+#[cfg(feature = "fs2")]
+pub use self::fs::with_locked_file;
 pub use self::fs::{
-    file_name_safe_timestamp, find_sentinel_dir, find_sentinel_file, label_file_name, open_file,
-    read_bytes, read_text_file, safe_back_up, safe_create_file, safe_write_file, FileReadError,
-    FileReadErrorKind, FileWriteError, FileWriteErrorKind, WorkingDirectory,
+    backup_path_for, copy_file_verified, copy_file_with_progress, copy_if_newer, count_lines,
+    detect_line_ending, ensure_parent_dir, expand_tilde, file_name_safe_timestamp, file_sha256,
+    find_files_with_extension, find_sentinel_dir, find_sentinel_dir_with_walk, find_sentinel_file,
+    find_sentinel_file_ci, find_sentinel_file_with_depth, find_sentinel_file_with_walk,
+    get_modified, hash_dir_tree, is_within, label_file_name, needs_backup, open_file, path_kind,
+    prune_backups_older_than, read_bytes, read_bytes_into, read_bytes_range, read_bytes_retry,
+    read_bytes_with_hash, read_link, read_nth_line, read_text_file, read_text_file_lossy,
+    read_text_file_no_bom, relativize, safe_back_up, safe_back_up_info, safe_back_up_many,
+    safe_back_up_with, safe_create_file, safe_remove_dir_all, safe_symlink, safe_write_file,
+    safe_write_file_like, safe_write_file_normalized, safe_write_text_file, stat, walk_dir,
+    AncestorWalk, BackupInfo, BackupNaming, FileInfo, FileReadError, FileReadErrorKind,
+    FileWriteError, FileWriteErrorKind, LineEnding, PathKind, SafeWriter, TempFile,
+    WorkingDirectory, WriteOptions, DEFAULT_SENTINEL_LIMIT,
 };
+#[cfg(feature = "flate2")]
+pub use self::fs::{safe_back_up_compressed, safe_back_up_compressed_default};
+#[cfg(feature = "test-util")]
+pub use self::test_util::assert_round_trips;
@@ -0,0 +1,98 @@
+// Copyright (c) 2023 Richard Cook
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the
+// "Software"), to deal in the Software without restriction, including
+// without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to
+// permit persons to whom the Software is furnished to do so, subject to
+// the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE
+// LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION
+// WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+//
+use crate::formats::{Format, Json, Toml, Yaml};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt::Debug;
+use std::path::Path;
+use tempdir::TempDir;
+
+/// Asserts that `value` survives being written to and read back from a
+/// file as JSON, YAML and TOML
+///
+/// Since TOML documents are tables, `value` must serialize as a struct or
+/// map
+///
+/// # Panics
+///
+/// Panics if any format fails to write or read `value` or if the value
+/// read back differs
+#[allow(unused)]
+pub fn assert_round_trips<T>(value: &T)
+where
+    T: Serialize + DeserializeOwned + PartialEq + Debug,
+{
+    let temp_dir = TempDir::new("joatmon-test").expect("must create temporary directory");
+    assert_round_trips_as::<Json, T>(&temp_dir.path().join("value.json"), value);
+    assert_round_trips_as::<Yaml, T>(&temp_dir.path().join("value.yaml"), value);
+    assert_round_trips_as::<Toml, T>(&temp_dir.path().join("value.toml"), value);
+}
+
+fn assert_round_trips_as<F, T>(path: &Path, value: &T)
+where
+    F: Format,
+    T: Serialize + DeserializeOwned + PartialEq + Debug,
+{
+    F::write(path, value, false)
+        .unwrap_or_else(|e| panic!("failed to write {}: {e}", path.display()));
+    let result =
+        F::read::<T>(path).unwrap_or_else(|e| panic!("failed to read {}: {e}", path.display()));
+    assert_eq!(
+        value,
+        &result,
+        "value did not round-trip through {}",
+        path.display()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::assert_round_trips;
+    use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Record {
+        message: String,
+        count: i64,
+        enabled: bool,
+        values: Vec<String>,
+        extra: BTreeMap<String, i64>,
+    }
+
+    #[test]
+    fn test_assert_round_trips() {
+        assert_round_trips(&Record {
+            message: String::from("hello-world"),
+            count: 5,
+            enabled: true,
+            values: vec![String::from("aaa"), String::from("bbb")],
+            extra: BTreeMap::from([(String::from("ccc"), 1), (String::from("ddd"), 2)]),
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "failed to write")]
+    fn test_assert_round_trips_unsupported_fails() {
+        assert_round_trips(&vec![1, 2, 3]);
+    }
+}